@@ -0,0 +1,145 @@
+use crate::println_pipe;
+use crate::registerer::WinRegistry;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use win_toast_core::artifacts::{self, Artifact, Artifacts};
+use win_toast_core::registration::{Change, Plan, Scope};
+
+fn artifacts_file() -> Result<PathBuf, String> {
+    let data_dir = dirs_next::data_local_dir().ok_or("Could not find local data dir")?;
+    Ok(artifacts::artifacts_path(&data_dir))
+}
+
+pub fn load() -> Result<Artifacts, String> {
+    match std::fs::read_to_string(artifacts_file()?) {
+        Ok(text) => Artifacts::parse(&text),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Artifacts::default()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Saves artifacts, the file and its directory are removed when nothing is left.
+fn save(artifacts: &Artifacts) -> Result<(), String> {
+    let path = artifacts_file()?;
+    let dir = path.parent().unwrap_or(&path);
+    if artifacts.is_empty() {
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.to_string()),
+            _ => {}
+        }
+        std::fs::remove_dir(dir).ok();
+        return Ok(());
+    }
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    std::fs::write(&path, artifacts.to_json()).map_err(|e| e.to_string())
+}
+
+/// Changes recorded artifacts. Failures are printed, they do not stop the command.
+pub fn update(change: impl FnOnce(&mut Artifacts)) {
+    let result = load().and_then(|mut artifacts| {
+        change(&mut artifacts);
+        save(&artifacts)
+    });
+    if let Err(e) = result {
+        println_pipe!("Could not update created artifacts: {}", e);
+    }
+}
+
+pub fn record(artifact: Artifact) {
+    update(|artifacts| artifacts.add(artifact));
+}
+
+pub fn forget(artifact: Artifact) {
+    update(|artifacts| artifacts.remove(&artifact));
+}
+
+/// Records the file or directory when it does not exist yet, existing ones are not ours.
+pub fn record_new(path: &Path, directory: bool) {
+    if path.exists() {
+        return;
+    }
+    let path = std::path::absolute(path)
+        .unwrap_or(path.to_path_buf())
+        .display()
+        .to_string();
+    record(match directory {
+        true => Artifact::Directory { path },
+        false => Artifact::File { path },
+    });
+}
+
+/// Removes a file or directory, missing ones count as removed.
+fn remove_path(artifact: &Artifact) -> std::io::Result<()> {
+    let result = match artifact {
+        Artifact::File { path } => std::fs::remove_file(path),
+        Artifact::Directory { path } => std::fs::remove_dir_all(path),
+        _ => Ok(()),
+    };
+    match result {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Removes all recorded artifacts, registry values and keys of both scopes first, then files and
+/// directories. Artifacts which could not be removed stay recorded for the next run.
+/// Returns true when machine scope changes were denied.
+pub fn uninstall(dry_run: bool) -> Result<bool, String> {
+    let artifacts = load()?;
+    let mut plans = vec![];
+    for scope in [Scope::User, Scope::Machine] {
+        let registry = WinRegistry::for_scope(scope);
+        plans.push((scope, artifacts::plan_remove(&registry, &artifacts, scope)?));
+    }
+    if dry_run {
+        let mut files = Plan::new("");
+        for artifact in artifacts.artifacts.iter() {
+            if let Artifact::File { path } | Artifact::Directory { path } = artifact {
+                if Path::new(path).exists() {
+                    files.changes.push(Change::DeleteFile(path.clone()));
+                }
+            }
+        }
+        plans.push((Scope::User, files));
+        let plans: Vec<&Plan> = plans.iter().map(|(_, plan)| plan).collect();
+        match plans.iter().filter(|plan| !plan.is_empty()).count() {
+            0 => print!("{}", Plan::new("")),
+            _ => plans.iter().for_each(|plan| {
+                if !plan.is_empty() {
+                    print!("{}", plan);
+                }
+            }),
+        }
+        return Ok(false);
+    }
+    let mut denied = false;
+    for (scope, plan) in plans {
+        let mut registry = WinRegistry::for_scope(scope);
+        for change in plan.changes {
+            let single = Plan {
+                root: plan.root.clone(),
+                changes: vec![change],
+            };
+            match single.apply(&mut registry) {
+                Ok(_) => {
+                    update(|artifacts| artifacts.remove_deleted(&single, Some(scope)));
+                    println_pipe!("{}", single.to_string().trim_end());
+                }
+                Err(e) if e.error.kind() == ErrorKind::PermissionDenied => denied = true,
+                Err(e) => println_pipe!("Could not remove {}: {}", e.target, e.error),
+            }
+        }
+    }
+    for artifact in artifacts.artifacts.iter() {
+        if let Artifact::File { path } | Artifact::Directory { path } = artifact {
+            match remove_path(artifact) {
+                Ok(_) => {
+                    forget(artifact.clone());
+                    println_pipe!("- file {}", path);
+                }
+                Err(e) => println_pipe!("Could not remove {}: {}", path, e),
+            }
+        }
+    }
+    Ok(denied)
+}
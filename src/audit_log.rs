@@ -0,0 +1,225 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+pub const DEFAULT_MAX_FILES: usize = 5;
+/// Content of records whose body is not logged.
+pub const REDACTED: &str = "[redacted]";
+
+#[derive(Debug, Clone)]
+pub struct AuditLogConfig {
+    pub path: PathBuf,
+    /// Size in bytes after which the log is rotated.
+    pub max_size: u64,
+    /// Number of rotated files kept next to the active one (`audit.log.1`, `audit.log.2`, ...).
+    pub max_files: usize,
+    /// Do not store request bodies, only their hashes.
+    pub redact: bool,
+}
+
+impl AuditLogConfig {
+    /// Active log and its rotated files.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        let rotated = (1..=self.max_files).map(|number| rotated_path(&self.path, number));
+        std::iter::once(self.path.clone()).chain(rotated).collect()
+    }
+}
+
+/// One line of the audit log.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct AuditRecord {
+    pub timestamp: String,
+    pub remote_addr: String,
+    pub key: Option<String>,
+    pub method: String,
+    pub route: String,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub notification_id: Option<String>,
+    pub content_hash: Option<String>,
+    pub content: Option<String>,
+}
+
+impl AuditRecord {
+    pub fn new(remote_addr: String, method: &str, route: &str) -> AuditRecord {
+        AuditRecord {
+            timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            remote_addr,
+            method: method.to_string(),
+            route: route.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Records the body and its hash. Bodies of `/admin/` routes may contain API keys, they are
+    /// replaced by [`REDACTED`] without a hash.
+    pub fn set_content(&mut self, body: &[u8]) {
+        if body.is_empty() {
+            return;
+        }
+        if self.route.starts_with("/admin/") {
+            self.content = Some(REDACTED.to_string());
+            return;
+        }
+        self.content_hash = Some(sha256_hex(body));
+        self.content = Some(String::from_utf8_lossy(body).to_string());
+    }
+}
+
+/// Append-only JSON lines log with size based rotation.
+pub struct AuditLog {
+    config: AuditLogConfig,
+    file: Mutex<Option<File>>,
+}
+
+impl AuditLog {
+    pub fn new(config: AuditLogConfig) -> AuditLog {
+        AuditLog {
+            config,
+            file: Mutex::new(None),
+        }
+    }
+
+    pub fn write(&self, record: &AuditRecord) -> std::io::Result<()> {
+        let mut record = record.clone();
+        if self.config.redact {
+            record.content = None;
+        }
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+        let mut guard = self
+            .file
+            .lock()
+            .map_err(|_| std::io::Error::other("audit log lock poisoned"))?;
+        let size = match guard.as_ref() {
+            Some(file) => file.metadata()?.len(),
+            None => fs::metadata(&self.config.path)
+                .map(|m| m.len())
+                .unwrap_or(0),
+        };
+        if size > 0 && size + line.len() as u64 > self.config.max_size {
+            guard.take();
+            rotate(&self.config.path, self.config.max_files)?;
+        }
+        if guard.is_none() {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.config.path)?;
+            guard.replace(file);
+        }
+        let file = guard.as_mut().unwrap();
+        file.write_all(line.as_bytes())?;
+        file.flush()
+    }
+}
+
+fn rotated_path(path: &Path, number: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", number));
+    PathBuf::from(name)
+}
+
+fn rotate(path: &Path, max_files: usize) -> std::io::Result<()> {
+    if max_files == 0 {
+        return fs::remove_file(path);
+    }
+    let oldest = rotated_path(path, max_files);
+    if oldest.exists() {
+        fs::remove_file(oldest)?;
+    }
+    for number in (1..max_files).rev() {
+        let from = rotated_path(path, number);
+        if from.exists() {
+            fs::rename(from, rotated_path(path, number + 1))?;
+        }
+    }
+    fs::rename(path, rotated_path(path, 1))
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Short non-reversible identifier of an API key, safe to store in logs.
+pub fn key_fingerprint(key: &[u8]) -> String {
+    sha256_hex(key)[..12].to_string()
+}
+
+#[cfg(test)]
+fn read_lines(path: &Path) -> Vec<serde_json::Value> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|x| serde_json::from_str(x).unwrap())
+        .collect()
+}
+
+#[test]
+fn audit_log_write_test() {
+    let dir = std::env::temp_dir().join(format!("audit-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("audit.log");
+    let log = AuditLog::new(AuditLogConfig {
+        path: path.clone(),
+        max_size: DEFAULT_MAX_SIZE,
+        max_files: DEFAULT_MAX_FILES,
+        redact: true,
+    });
+    let mut record = AuditRecord::new("127.0.0.1:5000".to_string(), "POST", "/notify");
+    record.set_content(b"{\"toast_xml\":\"<toast/>\"}");
+    record.status = Some(200);
+    log.write(&record).unwrap();
+    let lines = read_lines(&path);
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["route"], "/notify");
+    assert_eq!(lines[0]["status"], 200);
+    assert_eq!(lines[0]["content"], serde_json::Value::Null);
+    assert_eq!(
+        lines[0]["content_hash"],
+        sha256_hex(b"{\"toast_xml\":\"<toast/>\"}")
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn audit_admin_content_test() {
+    let body = b"{\"api_key\":\"new-secret-key\"}";
+    let mut record = AuditRecord::new("127.0.0.1:5000".to_string(), "POST", "/admin/rotate-key");
+    record.set_content(body);
+    assert_eq!(record.content.as_deref(), Some(REDACTED));
+    assert_eq!(record.content_hash, None);
+    let line = serde_json::to_string(&record).unwrap();
+    assert!(!line.contains("new-secret-key"));
+}
+
+#[test]
+fn audit_log_rotation_test() {
+    let dir = std::env::temp_dir().join(format!("audit-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("audit.log");
+    let log = AuditLog::new(AuditLogConfig {
+        path: path.clone(),
+        max_size: 300,
+        max_files: 2,
+        redact: false,
+    });
+    for i in 0..10 {
+        let record = AuditRecord::new(format!("client-{}", i), "GET", "/status-stream");
+        log.write(&record).unwrap();
+    }
+    assert!(path.exists());
+    assert!(rotated_path(&path, 1).exists());
+    assert!(rotated_path(&path, 2).exists());
+    assert!(!rotated_path(&path, 3).exists());
+    let last = read_lines(&path);
+    assert_eq!(last.last().unwrap()["remote_addr"], "client-9");
+    for file in [path.clone(), rotated_path(&path, 1)] {
+        assert!(fs::metadata(file).unwrap().len() <= 300);
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,140 @@
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+
+const PIPE_PREFIX: &str = "pipe:";
+const PIPE_PATH_PREFIX: &str = r"\\.\pipe\";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BindTarget {
+    Tcp(SocketAddr),
+    /// `127.0.0.1` with the port, and `::1` with the port bound on IPv4 when IPv6 is available.
+    Localhost(u16),
+    /// Full named pipe path, e.g. `\\.\pipe\win-toast-notifier`.
+    Pipe(String),
+}
+
+impl BindTarget {
+    pub fn describe(&self) -> serde_json::Value {
+        match self {
+            BindTarget::Tcp(addr) => serde_json::json!({
+                "ip": addr.ip().to_string(),
+                "port": addr.port(),
+            }),
+            BindTarget::Localhost(port) => serde_json::json!({
+                "host": "localhost",
+                "port": port,
+            }),
+            BindTarget::Pipe(path) => serde_json::json!({ "pipe": path }),
+        }
+    }
+}
+
+/// Parses a `--bind` value into one or more targets.
+///
+/// Accepted forms: `127.0.0.1`, `127.0.0.1:7070`, `::1`, `[::1]:7070`, `localhost`,
+/// `localhost:7070`, any resolvable host name, `pipe:<name>` and `\\.\pipe\<name>`.
+/// Values without a port use `default_port`.
+pub fn parse_bind(spec: &str, default_port: u16) -> Result<Vec<BindTarget>, String> {
+    let spec = spec.trim();
+    if spec.is_empty() {
+        return Err("bind address is empty".to_string());
+    }
+    if let Some(name) = spec.strip_prefix(PIPE_PREFIX) {
+        return pipe_target(name).map(|x| vec![x]);
+    }
+    if let Some(name) = spec.strip_prefix(PIPE_PATH_PREFIX) {
+        return pipe_target(name).map(|x| vec![x]);
+    }
+    if let Ok(addr) = spec.parse::<SocketAddr>() {
+        return Ok(vec![BindTarget::Tcp(addr)]);
+    }
+    let unbracketed = spec
+        .strip_prefix('[')
+        .and_then(|x| x.strip_suffix(']'))
+        .unwrap_or(spec);
+    if let Ok(ip) = unbracketed.parse::<IpAddr>() {
+        return Ok(vec![BindTarget::Tcp(SocketAddr::new(ip, default_port))]);
+    }
+    let (host, port) = match spec.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse::<u16>()
+                .map_err(|_| format!("invalid port in bind address [{}]", spec))?,
+        ),
+        None => (spec, default_port),
+    };
+    if host.eq_ignore_ascii_case("localhost") {
+        return Ok(vec![BindTarget::Localhost(port)]);
+    }
+    let mut targets: Vec<BindTarget> = vec![];
+    for addr in (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("could not resolve [{}]: {}", host, e))?
+    {
+        let target = BindTarget::Tcp(addr);
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+    if targets.is_empty() {
+        return Err(format!("[{}] did not resolve to any address", host));
+    }
+    Ok(targets)
+}
+
+fn pipe_target(name: &str) -> Result<BindTarget, String> {
+    if name.is_empty() || name.contains('\\') || name.contains('/') {
+        return Err(format!("invalid pipe name [{}]", name));
+    }
+    Ok(BindTarget::Pipe(format!("{}{}", PIPE_PATH_PREFIX, name)))
+}
+
+#[test]
+fn parse_ip_bind_test() {
+    assert_eq!(
+        parse_bind("127.0.0.1", 7070).unwrap(),
+        vec![BindTarget::Tcp("127.0.0.1:7070".parse().unwrap())]
+    );
+    assert_eq!(
+        parse_bind("0.0.0.0:80", 7070).unwrap(),
+        vec![BindTarget::Tcp("0.0.0.0:80".parse().unwrap())]
+    );
+    assert_eq!(
+        parse_bind("::1", 7070).unwrap(),
+        vec![BindTarget::Tcp("[::1]:7070".parse().unwrap())]
+    );
+    assert_eq!(
+        parse_bind("[::1]", 7070).unwrap(),
+        vec![BindTarget::Tcp("[::1]:7070".parse().unwrap())]
+    );
+    assert_eq!(
+        parse_bind("[::]:81", 7070).unwrap(),
+        vec![BindTarget::Tcp("[::]:81".parse().unwrap())]
+    );
+}
+
+#[test]
+fn parse_localhost_bind_test() {
+    assert_eq!(
+        parse_bind("localhost", 0).unwrap(),
+        vec![BindTarget::Localhost(0)]
+    );
+    assert_eq!(
+        parse_bind("LOCALHOST:7070", 0).unwrap(),
+        vec![BindTarget::Localhost(7070)]
+    );
+    assert!(parse_bind("localhost:port", 0).is_err());
+}
+
+#[test]
+fn parse_pipe_bind_test() {
+    assert_eq!(
+        parse_bind("pipe:toasts", 0).unwrap(),
+        vec![BindTarget::Pipe(r"\\.\pipe\toasts".to_string())]
+    );
+    assert_eq!(
+        parse_bind(r"\\.\pipe\toasts", 0).unwrap(),
+        vec![BindTarget::Pipe(r"\\.\pipe\toasts".to_string())]
+    );
+    assert!(parse_bind("pipe:", 0).is_err());
+    assert!(parse_bind(r"pipe:a\b", 0).is_err());
+}
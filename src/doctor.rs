@@ -0,0 +1,114 @@
+use crate::notifier::hs;
+use crate::registerer;
+use std::time::Duration;
+use tokio::time::sleep;
+use win_toast_core::doctor::{
+    self, Check, CheckStatus, Report, NOTIFIER_CHECK, PROBE_CHECK, REGISTRY_CHECK, SETTING_CHECK,
+};
+use win_toast_core::toast_xml;
+use windows::Data::Xml::Dom::XmlDocument;
+use windows::UI::Notifications::{ToastNotification, ToastNotificationManager, ToastNotifier};
+
+const PROBE_TAG: &str = "doctor-probe";
+const PROBE_GROUP: &str = "win-toast-notifier";
+
+/// Checks why toasts of the application id may not be shown, `probe` sends a toast.
+pub async fn diagnose(app_id: &str, probe: bool) -> Report {
+    let mut report = Report::new(app_id);
+    let is_path = std::fs::metadata(app_id).is_ok();
+    report.push(doctor::check_app_id(app_id, is_path));
+    match registerer::list() {
+        Ok((apps, shortcuts)) => {
+            for check in doctor::check_registration(&apps, &shortcuts, app_id) {
+                report.push(check);
+            }
+        }
+        Err(e) => report.push(Check::new(
+            REGISTRY_CHECK,
+            CheckStatus::Fail,
+            format!("could not read registrations: {}", e),
+        )),
+    }
+    let notifier = match ToastNotificationManager::CreateToastNotifierWithId(&hs(app_id)) {
+        Ok(notifier) => {
+            report.push(Check::new(NOTIFIER_CHECK, CheckStatus::Pass, "created"));
+            notifier
+        }
+        Err(e) => {
+            let detail = format!("CreateToastNotifierWithId failed: {}", e.message());
+            report.push(Check::new(NOTIFIER_CHECK, CheckStatus::Fail, detail));
+            report.push(Check::new(SETTING_CHECK, CheckStatus::Skip, "no notifier"));
+            report.push(Check::new(PROBE_CHECK, CheckStatus::Skip, "no notifier"));
+            return report;
+        }
+    };
+    match notifier.Setting() {
+        Ok(setting) => report.push(doctor::check_setting(setting.0)),
+        Err(e) => report.push(Check::new(
+            SETTING_CHECK,
+            CheckStatus::Warn,
+            format!("could not read setting: {}", e.message()),
+        )),
+    }
+    let probe_check = match probe {
+        false => Check::new(PROBE_CHECK, CheckStatus::Skip, "disabled by --no-probe"),
+        true if report.failed(SETTING_CHECK) => {
+            Check::new(PROBE_CHECK, CheckStatus::Skip, "notifications are disabled")
+        }
+        true => send_probe(app_id, &notifier).await,
+    };
+    report.push(probe_check);
+    report
+}
+
+/// Shows a probe toast and looks it up in the notification history of the application id.
+async fn send_probe(app_id: &str, notifier: &ToastNotifier) -> Check {
+    let xml = toast_xml::create_sample_notification(
+        "win-toast-notifier doctor",
+        "Toasts of this application are shown",
+        None,
+    );
+    let show = || -> windows::core::Result<()> {
+        let document = XmlDocument::new()?;
+        document.LoadXml(&hs(xml))?;
+        let toast = ToastNotification::CreateToastNotification(&document)?;
+        toast.SetTag(&hs(PROBE_TAG))?;
+        toast.SetGroup(&hs(PROBE_GROUP))?;
+        notifier.Show(&toast)
+    };
+    if let Err(e) = show() {
+        return Check::new(
+            PROBE_CHECK,
+            CheckStatus::Fail,
+            format!("could not show toast: {}", e.message()),
+        );
+    }
+    sleep(Duration::from_secs(1)).await;
+    let in_history = || -> windows::core::Result<bool> {
+        let history = ToastNotificationManager::History()?;
+        let toasts = history.GetHistoryWithId(&hs(app_id))?;
+        for i in 0..toasts.Size()? {
+            if toasts.GetAt(i)?.Tag()? == PROBE_TAG {
+                history.RemoveGroupedTagWithId(&hs(PROBE_TAG), &hs(PROBE_GROUP), &hs(app_id))?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    };
+    match in_history() {
+        Ok(true) => Check::new(PROBE_CHECK, CheckStatus::Pass, "shown"),
+        Ok(false) => Check::new(
+            PROBE_CHECK,
+            CheckStatus::Warn,
+            "sent but not found in the notification center, do not disturb may hide toasts",
+        ),
+        Err(e) => Check::new(
+            PROBE_CHECK,
+            CheckStatus::Warn,
+            format!(
+                "sent but notification history is not readable: {}",
+                e.message()
+            ),
+        ),
+    }
+}
@@ -0,0 +1,169 @@
+use crate::audit_log::key_fingerprint;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_GRACE_SECS: u64 = 300;
+
+/// API keys accepted by the listener: the current one and rotated out keys
+/// which are still valid until their grace period ends.
+#[derive(Debug, Default)]
+pub struct KeyRing {
+    current: Option<Box<[u8]>>,
+    previous: Vec<(Box<[u8]>, Instant)>,
+    /// Grace period used when rotation does not specify one.
+    pub default_grace: Duration,
+}
+
+impl KeyRing {
+    pub fn new(key: &str, default_grace: Duration) -> KeyRing {
+        KeyRing {
+            current: Some(key.as_bytes().to_vec().into_boxed_slice()),
+            previous: vec![],
+            default_grace,
+        }
+    }
+
+    /// Replaces current key, the old one stays valid for `grace`. Keys in their grace period keep
+    /// it, the new key is removed from them.
+    pub fn rotate(&mut self, key: &str, grace: Duration, now: Instant) {
+        self.previous.retain(|(previous, valid_until)| {
+            *valid_until > now && previous.as_ref() != key.as_bytes()
+        });
+        if let Some(old) = self.current.take() {
+            if !grace.is_zero() {
+                self.previous.push((old, now + grace));
+            }
+        }
+        self.current = Some(key.as_bytes().to_vec().into_boxed_slice());
+    }
+
+    /// Keys accepted at `now`, current key first.
+    pub fn valid_keys(&self, now: Instant) -> impl Iterator<Item = &[u8]> {
+        self.current.as_deref().into_iter().chain(
+            self.previous
+                .iter()
+                .rev()
+                .filter(move |(_, valid_until)| *valid_until > now)
+                .map(|(key, _)| key.as_ref()),
+        )
+    }
+
+    pub fn current_fingerprint(&self) -> Option<String> {
+        self.current.as_deref().map(key_fingerprint)
+    }
+
+    /// Key with the fingerprint is the current key, keys in their grace period are not.
+    pub fn is_current(&self, fingerprint: &str) -> bool {
+        self.current_fingerprint().as_deref() == Some(fingerprint)
+    }
+}
+
+/// Reads API key from the first configured source: value, environment variable or file.
+pub fn resolve_api_key(
+    value: Option<String>,
+    env_var: Option<String>,
+    file: Option<String>,
+) -> Result<Option<String>, String> {
+    if value.is_some() {
+        return Ok(value);
+    }
+    if let Some(name) = env_var {
+        let key = std::env::var(&name)
+            .map_err(|e| format!("Could not read api key from {}: {}", name, e))?;
+        return non_empty(key, &name).map(Some);
+    }
+    if let Some(path) = file {
+        let key = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Could not read api key from {}: {}", path, e))?;
+        return non_empty(key, &path).map(Some);
+    }
+    Ok(None)
+}
+
+fn non_empty(key: String, source: &str) -> Result<String, String> {
+    let key = key.trim().to_string();
+    if key.is_empty() {
+        return Err(format!("Api key in {} is empty", source));
+    }
+    Ok(key)
+}
+
+#[test]
+fn key_ring_rotation_test() {
+    let start = Instant::now();
+    let mut ring = KeyRing::new("first", Duration::from_secs(DEFAULT_GRACE_SECS));
+    ring.rotate("second", Duration::from_secs(60), start);
+    assert_eq!(
+        ring.valid_keys(start).collect::<Vec<_>>(),
+        vec![b"second".as_ref(), b"first".as_ref()]
+    );
+    ring.rotate(
+        "third",
+        Duration::from_secs(60),
+        start + Duration::from_secs(30),
+    );
+    assert_eq!(
+        ring.valid_keys(start + Duration::from_secs(59))
+            .collect::<Vec<_>>(),
+        vec![b"third".as_ref(), b"second".as_ref(), b"first".as_ref()]
+    );
+    assert_eq!(
+        ring.valid_keys(start + Duration::from_secs(60))
+            .collect::<Vec<_>>(),
+        vec![b"third".as_ref(), b"second".as_ref()]
+    );
+    ring.rotate("fourth", Duration::ZERO, start + Duration::from_secs(100));
+    assert_eq!(
+        ring.valid_keys(start + Duration::from_secs(100))
+            .collect::<Vec<_>>(),
+        vec![b"fourth".as_ref()]
+    );
+    assert_eq!(ring.current_fingerprint(), Some(key_fingerprint(b"fourth")));
+}
+
+#[test]
+fn key_ring_grace_test() {
+    let start = Instant::now();
+    let mut ring = KeyRing::new("first", Duration::from_secs(DEFAULT_GRACE_SECS));
+    ring.rotate("second", Duration::from_secs(60), start);
+    assert!(ring.is_current(&key_fingerprint(b"second")));
+    assert!(!ring.is_current(&key_fingerprint(b"first")));
+    ring.rotate("first", Duration::from_secs(60), start);
+    assert_eq!(
+        ring.valid_keys(start).collect::<Vec<_>>(),
+        vec![b"first".as_ref(), b"second".as_ref()]
+    );
+    ring.rotate(
+        "third",
+        Duration::from_secs(60),
+        start + Duration::from_secs(30),
+    );
+    assert_eq!(
+        ring.valid_keys(start + Duration::from_secs(60))
+            .collect::<Vec<_>>(),
+        vec![b"third".as_ref(), b"first".as_ref()]
+    );
+}
+
+#[test]
+fn resolve_api_key_test() {
+    assert_eq!(
+        resolve_api_key(Some("arg".to_string()), None, None),
+        Ok(Some("arg".to_string()))
+    );
+    assert_eq!(resolve_api_key(None, None, None), Ok(None));
+    let var = format!("WIN_TOAST_TEST_KEY_{}", uuid::Uuid::new_v4().simple());
+    std::env::set_var(&var, " from-env\n");
+    assert_eq!(
+        resolve_api_key(None, Some(var.clone()), None),
+        Ok(Some("from-env".to_string()))
+    );
+    std::env::remove_var(&var);
+    assert!(resolve_api_key(None, Some(var), None).is_err());
+    let path = std::env::temp_dir().join(format!("key-{}", uuid::Uuid::new_v4()));
+    std::fs::write(&path, "from-file\r\n").unwrap();
+    assert_eq!(
+        resolve_api_key(None, None, Some(path.display().to_string())),
+        Ok(Some("from-file".to_string()))
+    );
+    std::fs::remove_file(path).unwrap();
+}
@@ -1,24 +1,36 @@
 #![allow(unused_imports)]
 
+mod artifacts;
+mod audit_log;
+mod bind;
+mod doctor;
 mod elevator;
 mod elevator_values;
-mod event_log;
+mod key_ring;
 mod notifier;
 mod registerer;
-mod ring_buffer;
+mod signature;
 mod utils;
+mod xml_policy;
 
+use crate::audit_log::{AuditLog, AuditLogConfig, AuditRecord};
+use crate::bind::BindTarget;
 use crate::elevator::elevate;
 use crate::elevator::println_pipe;
-use crate::event_log::event_log;
+use crate::key_ring::KeyRing;
 use crate::notifier::NotificationConfig;
 use crate::notifier::{Notifier, ToastContent};
 use crate::registerer::RegistrationError;
+use crate::signature::{AuthMode, SignatureVerifier};
+use crate::xml_policy::XmlPathPolicy;
 use atoi::atoi;
+use chrono::{DateTime, Utc};
 use clap::builder::Str;
 use clap::{Parser, Subcommand};
-use hyper::body::Buf;
+use hyper::body::{Buf, Bytes, HttpBody};
+use hyper::server::conn::{AddrStream, Http};
 use hyper::service::{make_service_fn, service_fn};
+use hyper::http::request;
 use hyper::{header, Body, Method, Request, Response, Server, StatusCode};
 use lazy_static::lazy_static;
 use rand::{distributions::Alphanumeric, Rng};
@@ -31,25 +43,120 @@ use std::error::Error;
 use std::fmt::{format, Debug};
 use std::fs::File;
 use std::io::{Read, Write};
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::ops::Deref;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::fs;
+use tokio::net::windows::named_pipe::{NamedPipeServer, ServerOptions};
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::sync::Mutex;
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, oneshot, watch};
 use tokio::time::sleep;
 use url::form_urlencoded;
 use url::form_urlencoded::parse;
 use uuid::Uuid;
+use win_toast_core::event_log::{
+    self, event_log_with_retention, LagPolicy, LogItem, StartAt, SubscriberOptions,
+};
+use win_toast_core::event_store::{self, EventStoreConfig, FsyncPolicy, SegmentStore};
+use win_toast_core::export::{self, ExportFormat};
+use win_toast_core::model::{
+    self, DismissReason, NotificationActivationInfo, NotificationStatus, StatusEvent,
+};
+use win_toast_core::registration::{AppRegistration, Plan, Scope};
+use win_toast_core::ring_buffer::Retention;
+use win_toast_core::toast_xml;
 use winreg::enums::*;
 use winreg::RegKey;
 
 lazy_static! {
     static ref SHUTDOWN_TX: Arc<Mutex<Option<oneshot::Sender<()>>>> = <_>::default();
-    static ref API_KEY: Arc<RwLock<Option<Box<[u8]>>>> = <_>::default();
+    static ref API_KEY: Arc<RwLock<KeyRing>> = <_>::default();
+    static ref API_KEY_FILE: Arc<RwLock<Option<PathBuf>>> = <_>::default();
+    static ref AUDIT_LOG: Arc<RwLock<Option<AuditLog>>> = <_>::default();
+    static ref AUTH_MODE: Arc<RwLock<AuthMode>> = Arc::new(RwLock::new(AuthMode::Any));
+    static ref SHUTDOWN_SIGNAL: watch::Sender<bool> = watch::channel(false).0;
+    static ref STATUS_STREAM: Arc<RwLock<StatusStreamConfig>> = <_>::default();
+    static ref SIGNATURE_VERIFIER: Arc<RwLock<SignatureVerifier>> = Arc::new(RwLock::new(
+        SignatureVerifier::new(signature::DEFAULT_MAX_SKEW_SECS)
+    ));
+}
+
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+const DEFAULT_HEARTBEAT_SECS: u64 = 15;
+const DEFAULT_EVENTS_PAGE: usize = 100;
+const MAX_EVENTS_PAGE: usize = 1000;
+const DEFAULT_MAX_SUBSCRIBERS: usize = 64;
+
+static STATUS_STREAM_CLIENTS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy)]
+struct StatusStreamConfig {
+    /// Period of heartbeat lines sent while no events arrive.
+    heartbeat: Option<Duration>,
+    max_subscribers: usize,
+}
+
+impl Default for StatusStreamConfig {
+    fn default() -> Self {
+        StatusStreamConfig {
+            heartbeat: Some(Duration::from_secs(DEFAULT_HEARTBEAT_SECS)),
+            max_subscribers: DEFAULT_MAX_SUBSCRIBERS,
+        }
+    }
+}
+
+/// One of `max_subscribers` status-stream slots, released on drop.
+struct StatusStreamSlot;
+
+impl StatusStreamSlot {
+    fn acquire(max_subscribers: usize) -> Option<StatusStreamSlot> {
+        STATUS_STREAM_CLIENTS
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (count < max_subscribers).then_some(count + 1)
+            })
+            .ok()
+            .map(|_| StatusStreamSlot)
+    }
+}
+
+impl Drop for StatusStreamSlot {
+    fn drop(&mut self) {
+        STATUS_STREAM_CLIENTS.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Settings of the status event log.
+struct EventLogSettings {
+    retention: Retention,
+    store: Option<EventStoreConfig>,
+    subscriber_options: SubscriberOptions,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct RetentionArgs {
+    /// Maximum number of status events kept in memory
+    #[arg(long, default_value_t = event_log::DEFAULT_RETENTION_COUNT)]
+    event_retention_count: usize,
+    /// Maximum approximate size in bytes of status events kept in memory
+    #[arg(long)]
+    event_retention_bytes: Option<usize>,
+    /// Seconds status events are kept in memory
+    #[arg(long)]
+    event_retention_age: Option<u64>,
+}
+
+impl RetentionArgs {
+    fn retention(&self) -> Retention {
+        Retention {
+            max_count: self.event_retention_count,
+            max_bytes: self.event_retention_bytes,
+            max_age: self.event_retention_age.map(Duration::from_secs),
+        }
+    }
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -90,29 +197,99 @@ enum TestType {
 
 #[derive(Subcommand, Debug, Clone)]
 enum Commands {
-    /// Registers application_id in registry. Requires admin rights.
+    /// Registers application_id in registry. Machine scope requires admin rights.
     Register {
         /// Application Id. Example: com.app-name.module-name. See https://learn.microsoft.com/en-us/windows/win32/shell/appids
-        #[arg(short = 'a', long)]
-        application_id: String,
+        #[arg(short = 'a', long, required_unless_present = "manifest")]
+        application_id: Option<String>,
         /// Application display name (notification header)
         #[arg(short = 'n', long)]
         display_name: Option<String>,
-        /// Application icon path (notification icon)
+        /// Application icon path (notification icon), png, ico, jpg or svg
         #[arg(short = 'i', long)]
         icon_path: Option<String>,
+        /// Icon background color, #RRGGBB or AARRGGBB
+        #[arg(long)]
+        icon_background_color: Option<String>,
+        /// Whether the application is listed in notification settings
+        #[arg(long)]
+        show_in_settings: Option<bool>,
+        /// CLSID of the COM server activated when a toast is clicked
+        #[arg(long)]
+        custom_activator: Option<String>,
+        /// Command line starting the custom activator COM server
+        #[arg(long, requires = "custom_activator")]
+        launch_command: Option<String>,
+        /// Arguments of the Start menu shortcut created when application_id is an executable path
+        #[arg(long)]
+        shortcut_arguments: Option<String>,
+        /// Registry scope, user (HKEY_CURRENT_USER, default) or machine (HKEY_LOCAL_MACHINE, elevated).
+        /// With --manifest only applications of this scope are registered
+        #[arg(long)]
+        scope: Option<Scope>,
+        /// TOML manifest listing application ids to register, see README
+        #[arg(long, conflicts_with = "application_id")]
+        manifest: Option<String>,
+        /// Unregister application ids created by this tool which are not listed in the manifest
+        #[arg(long, requires = "manifest")]
+        prune: bool,
+        /// Write the registry changes as a reg, powershell or json file for deployment instead of applying them
+        #[arg(long, conflicts_with_all = ["prune", "parent_pipe", "dry_run"])]
+        export: Option<ExportFormat>,
+        /// File written by --export, printed to stdout by default
+        #[arg(long, requires = "export")]
+        output: Option<PathBuf>,
         /// Output pipe name
         #[arg(short = 'p', long)]
         parent_pipe: Option<String>,
+        /// Print planned registry and shortcut changes as a diff without applying them
+        #[arg(long)]
+        dry_run: bool,
     },
-    /// Removes application_id registration in registry.
+    /// Removes application_id registration in registry, --all removes everything created by this tool.
     UnRegister {
         /// Application Id.
-        #[arg(short = 'a', long)]
-        application_id: String,
+        #[arg(short = 'a', long, required_unless_present = "all")]
+        application_id: Option<String>,
+        /// Registry scope, user (HKEY_CURRENT_USER) or machine (HKEY_LOCAL_MACHINE, elevated)
+        #[arg(long, default_value = "user")]
+        scope: Scope,
+        /// Remove every registry key, shortcut, icon and file created by this tool in any scope
+        #[arg(long, conflicts_with_all = ["application_id", "scope"])]
+        all: bool,
+        /// Write the registry changes as a reg, powershell or json file for deployment instead of applying them
+        #[arg(long, conflicts_with_all = ["all", "parent_pipe", "dry_run"])]
+        export: Option<ExportFormat>,
+        /// File written by --export, printed to stdout by default
+        #[arg(long, requires = "export")]
+        output: Option<PathBuf>,
+        /// CLSID of the custom activator whose launch command is removed by --export
+        #[arg(long, requires = "export")]
+        custom_activator: Option<String>,
         /// Output pipe name
         #[arg(short = 'p', long)]
         parent_pipe: Option<String>,
+        /// Print planned registry and shortcut changes as a diff without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Lists registered application ids and Start menu shortcuts.
+    List {
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+    /// Checks why toasts of application_id are not shown and sends a probe toast.
+    Doctor {
+        /// Application Id.
+        #[arg(short = 'a', long)]
+        application_id: String,
+        /// Do not send the probe toast
+        #[arg(long)]
+        no_probe: bool,
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
     },
     /// Creates sample notification.
     Test {
@@ -122,6 +299,8 @@ enum Commands {
         /// Wait.
         #[arg(long)]
         wait: bool,
+        #[command(flatten)]
+        retention: RetentionArgs,
         // Type
         #[command(subcommand)]
         test_type: TestType,
@@ -134,12 +313,83 @@ enum Commands {
         /// HTTP API key, should be specified in api-key header
         #[arg(short = 'k', long)]
         api_key: Option<String>,
+        /// Environment variable to read HTTP API key from
+        #[arg(long, conflicts_with = "api_key")]
+        api_key_env: Option<String>,
+        /// File to read HTTP API key from
+        #[arg(long, conflicts_with_all = ["api_key", "api_key_env"])]
+        api_key_file: Option<String>,
+        /// File readable only by current user to write HTTP API key to, instead of stdout.
+        /// Rotated keys are written there as well
+        #[arg(long)]
+        api_key_out: Option<PathBuf>,
+        /// Seconds the previous key stays valid after rotation via /admin/rotate-key
+        #[arg(long, default_value_t = key_ring::DEFAULT_GRACE_SECS)]
+        api_key_grace: u64,
+        /// Accepted authorization: api-key header, HMAC request signature or any of them
+        #[arg(long, value_enum, default_value_t = AuthMode::Any)]
+        auth_mode: AuthMode,
+        /// Allowed difference in seconds between X-Timestamp of signed request and server clock
+        #[arg(long, default_value_t = signature::DEFAULT_MAX_SKEW_SECS)]
+        signature_max_skew: u64,
         /// TCP port to listen on
         #[arg(short, long, default_value_t = 0)]
         port: u16,
-        /// IP Address to listen on
+        /// IP Address to listen on. Ignored when --bind is specified
         #[arg(short, long, default_value = "127.0.0.1")]
         ip: String,
+        /// Address to listen on, can be repeated. Accepts IPv4, IPv6, host names (localhost),
+        /// optional :port suffix and pipe:<name> for a local named pipe
+        #[arg(short, long)]
+        bind: Vec<String>,
+        /// Directory toast_xml_path files may be read from, can be repeated.
+        /// Without it only --xml-base-dir is allowed, without both toast_xml_path is rejected
+        #[arg(long)]
+        allowed_xml_dir: Vec<String>,
+        /// Directory relative toast_xml_path values are resolved against
+        #[arg(long)]
+        xml_base_dir: Option<String>,
+        /// Maximum size of toast_xml_path file in bytes
+        #[arg(long, default_value_t = xml_policy::DEFAULT_MAX_XML_SIZE)]
+        max_xml_size: u64,
+        /// Path of JSON lines audit log of handled API requests
+        #[arg(long)]
+        audit_log: Option<PathBuf>,
+        /// Audit log size in bytes after which it is rotated
+        #[arg(long, default_value_t = audit_log::DEFAULT_MAX_SIZE)]
+        audit_log_max_size: u64,
+        /// Number of rotated audit log files to keep
+        #[arg(long, default_value_t = audit_log::DEFAULT_MAX_FILES)]
+        audit_log_max_files: usize,
+        /// Store only hashes of request bodies in audit log
+        #[arg(long)]
+        audit_log_redact: bool,
+        /// Directory to persist status events in, so numbering and replay survive restarts
+        #[arg(long)]
+        event_log_dir: Option<PathBuf>,
+        /// When persisted events are synced to disk: always, never or after N events
+        #[arg(long, default_value = "always")]
+        event_log_fsync: FsyncPolicy,
+        /// Size in bytes of one persisted events segment file
+        #[arg(long, default_value_t = event_store::DEFAULT_SEGMENT_SIZE)]
+        event_log_segment_size: u64,
+        /// Number of persisted events segment files to keep
+        #[arg(long, default_value_t = event_store::DEFAULT_MAX_SEGMENTS)]
+        event_log_max_segments: usize,
+        /// What to do when a status-stream client falls behind: disconnect, drop-oldest or resume
+        #[arg(long, default_value = "resume")]
+        subscriber_lag_policy: LagPolicy,
+        /// Number of status events queued per status-stream client before lag policy applies
+        #[arg(long, default_value_t = event_log::DEFAULT_SUBSCRIBER_QUEUE_LEN)]
+        subscriber_queue: usize,
+        /// Seconds between heartbeat lines of an idle status-stream, 0 disables heartbeats
+        #[arg(long, default_value_t = DEFAULT_HEARTBEAT_SECS)]
+        heartbeat_secs: u64,
+        /// Maximum number of concurrent status-stream clients
+        #[arg(long, default_value_t = DEFAULT_MAX_SUBSCRIBERS)]
+        max_subscribers: usize,
+        #[command(flatten)]
+        retention: RetentionArgs,
     },
 }
 
@@ -149,6 +399,14 @@ struct NotificationRequest {
     toast_xml: Option<String>,
     #[serde(default)]
     toast_xml_path: Option<String>,
+    #[serde(default)]
+    tag: Option<String>,
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default)]
+    expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    deliver_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -162,70 +420,216 @@ enum WorkerMessage {
     HideAllNotifications(Sender<Result<(), String>>),
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize)]
-pub struct NotificationActivationInfo {
-    arguments: String,
-    inputs: HashMap<String, String>,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize)]
-pub enum DismissReason {
-    UserCanceled,
-    ApplicationHidden,
-    TimedOut,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize)]
-pub enum NotificationStatus {
-    Activated(String, NotificationActivationInfo),
-    Dismissed(String, DismissReason),
-    DismissedError(String, String),
-    Failed(String, String),
-}
-
 #[tokio::main]
 async fn main() {
+    model::start_clock();
     let args = Args::parse();
     match args.command {
         Commands::Register {
             application_id,
             display_name,
             icon_path,
+            icon_background_color,
+            show_in_settings,
+            custom_activator,
+            launch_command,
+            shortcut_arguments,
+            scope,
+            manifest,
+            prune,
+            export,
+            output,
             parent_pipe,
+            dry_run,
         } => {
-            register(application_id, display_name, icon_path, &parent_pipe).await;
+            if let (Some(manifest), Some(format)) = (&manifest, export) {
+                let plans = registerer::read_manifest(Path::new(manifest))
+                    .and_then(|manifest| export::plan_export_manifest(&manifest, scope));
+                write_export(plans, format, output);
+                return;
+            }
+            if let Some(manifest) = manifest {
+                register_manifest(manifest, scope, prune, &parent_pipe, dry_run).await;
+                return;
+            }
+            let registration = AppRegistration {
+                app_id: application_id.expect("application id is required"),
+                display_name,
+                icon_path,
+                icon_background_color,
+                show_in_settings,
+                custom_activator,
+                launch_command,
+            };
+            let scope = scope.unwrap_or_default();
+            if let Some(format) = export {
+                let plans =
+                    export::plan_export_register(&registration, scope).map(|plan| vec![plan]);
+                write_export(plans, format, output);
+                return;
+            }
+            register(registration, shortcut_arguments, scope, &parent_pipe, dry_run).await;
         }
         Commands::UnRegister {
             application_id,
+            scope,
+            all,
+            export,
+            output,
+            custom_activator,
             parent_pipe,
+            dry_run,
         } => {
-            un_register(application_id, &parent_pipe).await;
+            if all {
+                uninstall(&parent_pipe, dry_run).await;
+                return;
+            }
+            let application_id = application_id.expect("application id is required");
+            if let Some(format) = export {
+                let activator = custom_activator.as_deref();
+                let plans = export::plan_export_unregister(&application_id, activator, scope)
+                    .map(|plan| vec![plan]);
+                write_export(plans, format, output);
+                return;
+            }
+            un_register(application_id, scope, &parent_pipe, dry_run).await;
+        }
+        Commands::List { json } => {
+            list(json);
+        }
+        Commands::Doctor {
+            application_id,
+            no_probe,
+            json,
+        } => {
+            let report = doctor::diagnose(&application_id, !no_probe).await;
+            match json {
+                true => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+                false => print!("{}", report),
+            }
+            if !report.passed {
+                std::process::exit(1);
+            }
         }
         Commands::Listen {
             application_id,
             api_key,
+            api_key_env,
+            api_key_file,
+            api_key_out,
+            api_key_grace,
+            auth_mode,
+            signature_max_skew,
             port,
             ip,
+            bind,
+            allowed_xml_dir,
+            xml_base_dir,
+            max_xml_size,
+            audit_log,
+            audit_log_max_size,
+            audit_log_max_files,
+            audit_log_redact,
+            event_log_dir,
+            event_log_fsync,
+            event_log_segment_size,
+            event_log_max_segments,
+            subscriber_lag_policy,
+            subscriber_queue,
+            heartbeat_secs,
+            max_subscribers,
+            retention,
         } => {
-            listen(application_id, api_key, port, ip).await;
+            let xml_policy = XmlPathPolicy::new(allowed_xml_dir, xml_base_dir, max_xml_size)
+                .expect("invalid toast xml path configuration");
+            let show_api_key =
+                api_key_env.is_none() && api_key_file.is_none() && api_key_out.is_none();
+            let api_key = key_ring::resolve_api_key(api_key, api_key_env, api_key_file)
+                .expect("invalid api key configuration");
+            let api_key = api_key.unwrap_or(utils::get_random_string(50));
+            if let Some(path) = &api_key_out {
+                artifacts::record_new(path, false);
+                utils::write_user_only_file(path, &api_key).expect("Could not write api key");
+            }
+            if let Ok(mut guard) = API_KEY.write() {
+                *guard = KeyRing::new(&api_key, Duration::from_secs(api_key_grace));
+            }
+            if let Ok(mut guard) = API_KEY_FILE.write() {
+                *guard = api_key_out.clone();
+            }
+            if let Ok(mut guard) = AUTH_MODE.write() {
+                *guard = auth_mode;
+            }
+            if let Ok(mut guard) = SIGNATURE_VERIFIER.write() {
+                *guard = SignatureVerifier::new(signature_max_skew);
+            }
+            if let Some(path) = audit_log {
+                let config = AuditLogConfig {
+                    path,
+                    max_size: audit_log_max_size,
+                    max_files: audit_log_max_files,
+                    redact: audit_log_redact,
+                };
+                for path in config.paths() {
+                    artifacts::record_new(&path, false);
+                }
+                if let Ok(mut guard) = AUDIT_LOG.write() {
+                    guard.replace(AuditLog::new(config));
+                }
+            }
+            if let Some(dir) = &event_log_dir {
+                artifacts::record_new(dir, true);
+            }
+            let api_key_info = match (show_api_key, api_key_out) {
+                (true, _) => json!({ "api_key": api_key }),
+                (false, Some(path)) => json!({ "api_key_file": path }),
+                (false, None) => json!({}),
+            };
+            let event_log = EventLogSettings {
+                retention: retention.retention(),
+                store: event_log_dir.map(|dir| EventStoreConfig {
+                    dir,
+                    fsync: event_log_fsync,
+                    segment_size: event_log_segment_size,
+                    max_segments: event_log_max_segments,
+                }),
+                subscriber_options: SubscriberOptions {
+                    queue_len: subscriber_queue,
+                    lag_policy: subscriber_lag_policy,
+                },
+            };
+            if let Ok(mut guard) = STATUS_STREAM.write() {
+                *guard = StatusStreamConfig {
+                    heartbeat: Some(Duration::from_secs(heartbeat_secs)).filter(|x| !x.is_zero()),
+                    max_subscribers,
+                };
+            }
+            listen(
+                application_id,
+                api_key_info,
+                port,
+                ip,
+                bind,
+                xml_policy,
+                event_log,
+            )
+            .await;
         }
         Commands::Test {
             application_id,
             wait,
+            retention,
             test_type,
         } => {
-            test(&application_id, wait, test_type).await;
+            test(&application_id, wait, retention.retention(), test_type).await;
         }
     }
 }
 
-async fn test(application_id: &String, wait: bool, test_type: TestType) {
-    let (n_sender, mut n_recv) = event_log::<NotificationStatus>(1000);
-    tokio::spawn(async move {
-        n_recv.init_transport().await;
-    });
-    let mut notifier =
-        Notifier::new(&application_id, n_sender.clone()).expect("Could not create notifier");
+async fn test(application_id: &String, wait: bool, retention: Retention, test_type: TestType) {
+    let n_sender = event_log_with_retention::<StatusEvent>(retention, None);
+    let mut notifier = Notifier::new(&application_id, n_sender.clone(), XmlPathPolicy::deny_all())
+        .expect("Could not create notifier");
     let content = match test_type {
         TestType::Simple {
             title,
@@ -234,7 +638,7 @@ async fn test(application_id: &String, wait: bool, test_type: TestType) {
             debug,
         } => {
             let string =
-                utils::create_sample_notification(title.as_str(), message.as_str(), buttons);
+                toast_xml::create_sample_notification(title.as_str(), message.as_str(), buttons);
             if debug {
                 println!("xml:");
                 println!("{}", string);
@@ -244,67 +648,209 @@ async fn test(application_id: &String, wait: bool, test_type: TestType) {
         TestType::Raw { xml } => ToastContent::Raw(xml),
         TestType::RawFile { xml_path } => ToastContent::Raw(fs::read_to_string(xml_path).await.unwrap()),
     };
-    registerer::register_app_id_fallback(&application_id).unwrap();
+    registerer::register_app_id_fallback(&AppRegistration::new(&application_id), None, false)
+        .unwrap();
+    let mut subscriber = n_sender.subscribe_tail(0).await;
     notifier
-        .notify(NotificationConfig { content })
+        .notify(NotificationConfig::new(content))
         .expect("something was wrong");
     if wait {
-        if let Some((num, res)) = n_sender.subscribe().await.recv().await {
+        while let Some(item) = subscriber.recv().await {
+            let LogItem::Event(num, res) = item else {
+                continue;
+            };
+            if matches!(
+                res.status,
+                NotificationStatus::Shown(..)
+                    | NotificationStatus::Scheduled(..)
+                    | NotificationStatus::Replaced(..)
+            ) {
+                continue;
+            }
             println!(
                 "{}",
                 json!({
                     "event_number": num,
-                    "event": res
+                    "event": *res
                 })
                     .to_string()
             );
+            break;
         }
     } else {
         sleep(Duration::from_secs(1)).await
     }
 }
 
-async fn un_register(application_id: String, parent_pipe: &Option<String>) {
+async fn un_register(
+    application_id: String,
+    scope: Scope,
+    parent_pipe: &Option<String>,
+    dry_run: bool,
+) {
     if let Some(pipe_name) = &parent_pipe {
         elevator::enable_pipe_output(pipe_name.to_string());
         println_pipe!("Started as elevated");
     }
     if std::fs::metadata(&application_id).is_ok() {
-        registerer::un_register_app_id_fallback(&application_id).expect("Failed to unregister");
+        registerer::un_register_app_id_fallback(&application_id, dry_run)
+            .expect("Failed to unregister");
         return;
     }
-    if let Err(RegistrationError::FileError(e, _f)) =
-        registerer::unregister_app_id(application_id.clone())
-    {
-        if parent_pipe.is_some() {
+    let result = registerer::unregister_app_id(application_id.clone(), scope, dry_run);
+    if let Err(RegistrationError::ArgumentError(msg)) = &result {
+        println_pipe!("{}", msg);
+    }
+    if let Err(RegistrationError::FileError(e, _f)) = result {
+        if parent_pipe.is_some() || scope == Scope::User {
             println!("Failed to unregister: {}", e.to_string());
         } else {
-            registerer::run_elevated("un-register", application_id, None, None)
+            let mut args = vec!["-a".to_string(), application_id];
+            args.extend(registerer::scope_args(scope));
+            registerer::run_elevated("un-register", args)
                 .await
                 .expect("Failed to run as admin");
         }
     }
 }
 
+/// Writes the plans in `format` to `output` or stdout.
+fn write_export(plans: Result<Vec<Plan>, String>, format: ExportFormat, output: Option<PathBuf>) {
+    let plans = match plans {
+        Ok(plans) => plans,
+        Err(msg) => {
+            println!("{}", msg);
+            return;
+        }
+    };
+    let text = export::export(&plans, format);
+    match output {
+        Some(path) => {
+            std::fs::write(&path, format.encode(&text)).expect("Failed to write export");
+            println!("Exported to {}", path.display());
+        }
+        None => print!("{}", text),
+    }
+}
+
+async fn uninstall(parent_pipe: &Option<String>, dry_run: bool) {
+    if let Some(pipe_name) = &parent_pipe {
+        elevator::enable_pipe_output(pipe_name.to_string());
+        println_pipe!("Started as elevated");
+    }
+    let denied = artifacts::uninstall(dry_run).expect("Failed to uninstall");
+    if !denied {
+        return;
+    }
+    if parent_pipe.is_some() {
+        println_pipe!("Failed to remove machine scope registrations: access denied");
+    } else {
+        registerer::run_elevated("un-register", vec!["--all".to_string()])
+            .await
+            .expect("Failed to run as admin");
+    }
+}
+
+fn list(json: bool) {
+    let (apps, shortcuts) = registerer::list().expect("Failed to list registrations");
+    if json {
+        let list = json!({ "registrations": apps, "shortcuts": shortcuts });
+        println!("{}", serde_json::to_string_pretty(&list).unwrap());
+        return;
+    }
+    let created = |created_by_tool: bool| match created_by_tool {
+        true => " [created by win-toast-notifier]",
+        false => "",
+    };
+    let icon = |icon: &Option<String>, exists: bool| match (icon, exists) {
+        (Some(icon), true) => format!(", icon {}", icon),
+        (Some(icon), false) => format!(", icon {} (missing)", icon),
+        (None, _) => String::new(),
+    };
+    println!("Registrations:");
+    for app in apps.iter() {
+        println!(
+            "  {} ({}) \"{}\"{}{}",
+            app.application_id,
+            app.scope,
+            app.display_name.as_deref().unwrap_or(""),
+            icon(&app.icon_uri, app.icon_exists),
+            created(app.created_by_tool)
+        );
+    }
+    println!("Shortcuts:");
+    for shortcut in shortcuts.iter() {
+        println!(
+            "  {} -> {}{}{}",
+            shortcut.path,
+            shortcut.target.as_deref().unwrap_or("?"),
+            icon(&shortcut.icon, shortcut.icon_exists),
+            created(shortcut.created_by_tool)
+        );
+    }
+}
+
+async fn register_manifest(
+    path: String,
+    scope: Option<Scope>,
+    prune: bool,
+    parent_pipe: &Option<String>,
+    dry_run: bool,
+) {
+    if let Some(pipe_name) = &parent_pipe {
+        elevator::enable_pipe_output(pipe_name.to_string());
+        println_pipe!("Started as elevated");
+    }
+    let path = std::fs::canonicalize(&path).expect("Failed to find manifest");
+    let manifest = registerer::read_manifest(&path).expect("Failed to read manifest");
+    let scopes = match scope {
+        Some(scope) => vec![scope],
+        None => vec![Scope::User, Scope::Machine],
+    };
+    for scope in scopes {
+        match registerer::sync_manifest(&manifest, scope, prune, dry_run) {
+            Ok(_) => {}
+            Err(RegistrationError::ArgumentError(msg)) => {
+                println_pipe!("{}", msg);
+                return;
+            }
+            Err(RegistrationError::FileError(e, file)) => {
+                if parent_pipe.is_some() || scope == Scope::User {
+                    println_pipe!("Failed to register: {} {}", e, file);
+                    return;
+                }
+                println!("Failed to register: {}", e);
+                let mut args = vec!["--manifest".to_string(), path.display().to_string()];
+                args.extend(registerer::scope_args(scope));
+                if prune {
+                    args.push("--prune".to_string());
+                }
+                registerer::run_elevated("register", args)
+                    .await
+                    .expect("Cant run elevated");
+            }
+        }
+    }
+}
+
 async fn register(
-    application_id: String,
-    display_name: Option<String>,
-    icon_path: Option<String>,
+    registration: AppRegistration,
+    shortcut_arguments: Option<String>,
+    scope: Scope,
     parent_pipe: &Option<String>,
+    dry_run: bool,
 ) {
     if let Some(pipe_name) = &parent_pipe {
         elevator::enable_pipe_output(pipe_name.to_string());
         println_pipe!("Started as elevated");
     }
-    if std::fs::metadata(&application_id).is_ok() && display_name.is_none() {
-        registerer::register_app_id_fallback(&application_id).expect("Failed to register");
+    if std::fs::metadata(&registration.app_id).is_ok() && registration.display_name.is_none() {
+        registerer::register_app_id_fallback(&registration, shortcut_arguments, dry_run)
+            .expect("Failed to register");
         return;
     }
-    match registerer::register_app_id(
-        application_id.clone(),
-        display_name.clone(),
-        icon_path.clone(),
-    ) {
+    match registerer::register_app_id(&registration, scope, dry_run) {
+        Ok(_) if dry_run => {}
         Ok(_) => {
             println_pipe!("Done");
         }
@@ -313,14 +859,13 @@ async fn register(
                 if parent_pipe.is_some() {
                     println_pipe!("{} {}", e.to_string(), file);
                     panic!("Error: {} for {}", e.to_string(), file)
+                } else if scope == Scope::User {
+                    println!("Failed to register: {} {}", e, file);
                 } else {
                     println!("Failed to register: {}", e.to_string());
-                    registerer::run_elevated(
-                        "register",
-                        application_id.clone(),
-                        display_name,
-                        icon_path,
-                    )
+                    let mut args = registerer::register_args(&registration);
+                    args.extend(registerer::scope_args(scope));
+                    registerer::run_elevated("register", args)
                         .await
                         .expect("Cant run elevated");
                 }
@@ -332,7 +877,15 @@ async fn register(
     };
 }
 
-async fn listen(application_id: Option<String>, api_key: Option<String>, port: u16, ip: String) {
+async fn listen(
+    application_id: Option<String>,
+    api_key_info: serde_json::Value,
+    port: u16,
+    ip: String,
+    bind: Vec<String>,
+    xml_policy: XmlPathPolicy,
+    event_log: EventLogSettings,
+) {
     let application_id = match application_id {
         None => current_exe()
             .unwrap()
@@ -343,60 +896,200 @@ async fn listen(application_id: Option<String>, api_key: Option<String>, port: u
             .to_string(),
         Some(id) => id.to_string(),
     };
-    registerer::register_app_id_fallback(&application_id).unwrap();
-    let (tx, rx) = oneshot::channel::<()>();
-    SHUTDOWN_TX.lock().await.replace(tx);
-    let api_key = api_key.unwrap_or(utils::get_random_string(50));
-    match API_KEY.write() {
-        Ok(mut guard) => {
-            guard.replace(api_key.as_bytes().to_vec().into_boxed_slice());
+    let bind = if bind.is_empty() { vec![ip] } else { bind };
+    let mut targets: Vec<BindTarget> = vec![];
+    for spec in bind.iter() {
+        for target in bind::parse_bind(spec, port).expect("invalid bind address") {
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
         }
-        Err(_) => {}
     }
-    let addr = SocketAddr::from((ip.parse::<Ipv4Addr>().expect("invalid ip address"), port));
-    let (w_sender, w_receiver) = mpsc::channel::<WorkerMessage>(32);
-    let (n_sender, mut n_recv) = event_log::<NotificationStatus>(1000);
+    registerer::register_app_id_fallback(&AppRegistration::new(&application_id), None, false)
+        .unwrap();
+    let (tx, rx) = oneshot::channel::<()>();
+    SHUTDOWN_TX.lock().await.replace(tx);
+    let shutdown_rx = SHUTDOWN_SIGNAL.subscribe();
     tokio::spawn(async move {
-        n_recv.init_transport().await;
+        rx.await.ok();
+        SHUTDOWN_SIGNAL.send_replace(true);
     });
-    let notifier =
-        Notifier::new(&application_id, n_sender.clone()).expect("Could not create notifier");
+    let (w_sender, w_receiver) = mpsc::channel::<WorkerMessage>(32);
+    let store = event_log.store.map(|config| {
+        let store = SegmentStore::<StatusEvent>::open(config)
+            .expect("Could not open event log directory");
+        Box::new(store) as Box<dyn event_store::EventPersistence<StatusEvent>>
+    });
+    let n_sender = event_log_with_retention::<StatusEvent>(event_log.retention, store)
+        .with_subscriber_options(event_log.subscriber_options);
+    let notifier = Notifier::new(&application_id, n_sender.clone(), xml_policy)
+        .expect("Could not create notifier");
     let processing_task = tokio::spawn(async move {
         process_notification_api_messages(notifier, w_receiver).await;
     });
-    let make_svc = make_service_fn(move |_conn| {
+    let mut bindings = vec![];
+    let mut server_tasks = vec![];
+    for target in targets {
+        let w_sender = w_sender.clone();
+        let n_sender = n_sender.clone();
+        let shutdown_rx = shutdown_rx.clone();
+        match target {
+            BindTarget::Tcp(addr) => {
+                let (local_addr, task) = serve_tcp(addr, w_sender, n_sender, shutdown_rx)
+                    .unwrap_or_else(|e| panic!("Could not bind {}: {}", addr, e));
+                bindings.push(BindTarget::Tcp(local_addr));
+                server_tasks.push(task);
+            }
+            BindTarget::Localhost(port) => {
+                let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+                let (local_addr, task) = serve_tcp(
+                    addr,
+                    w_sender.clone(),
+                    n_sender.clone(),
+                    shutdown_rx.clone(),
+                )
+                .unwrap_or_else(|e| panic!("Could not bind {}: {}", addr, e));
+                bindings.push(BindTarget::Tcp(local_addr));
+                server_tasks.push(task);
+                let addr = SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), local_addr.port());
+                match serve_tcp(addr, w_sender, n_sender, shutdown_rx) {
+                    Ok((local_addr, task)) => {
+                        bindings.push(BindTarget::Tcp(local_addr));
+                        server_tasks.push(task);
+                    }
+                    Err(e) => eprintln!(
+                        "Could not bind {}, localhost is served on IPv4 only: {}",
+                        addr, e
+                    ),
+                }
+            }
+            BindTarget::Pipe(path) => {
+                let pipe = ServerOptions::new()
+                    .first_pipe_instance(true)
+                    .create(&path)
+                    .unwrap_or_else(|e| panic!("Could not create pipe {}: {}", path, e));
+                bindings.push(BindTarget::Pipe(path.clone()));
+                server_tasks.push(tokio::spawn(async move {
+                    serve_pipe(path, pipe, w_sender, n_sender, shutdown_rx)
+                        .await
+                        .expect("Pipe server failed");
+                }));
+            }
+        }
+    }
+    drop(w_sender);
+    let first_tcp = bindings.iter().find_map(|x| match x {
+        BindTarget::Tcp(addr) => Some(addr),
+        BindTarget::Localhost(_) | BindTarget::Pipe(_) => None,
+    });
+    let mut info = json!({
+        "ip": first_tcp.map(|x| x.ip().to_string()),
+        "port": first_tcp.map(|x| x.port()),
+        "bindings": bindings.iter().map(|x| x.describe()).collect::<Vec<_>>(),
+        "application_id": application_id,
+        "api_key_fingerprint": current_key_identity(),
+    });
+    if let (Some(info), Some(api_key_info)) = (info.as_object_mut(), api_key_info.as_object()) {
+        info.extend(api_key_info.clone());
+    }
+    println!("{}", info.to_string());
+    for task in server_tasks {
+        task.await.unwrap();
+    }
+    processing_task.await.unwrap();
+}
+
+/// Serves the http api on the address, returns the bound address and the server task.
+fn serve_tcp(
+    addr: SocketAddr,
+    w_sender: Sender<WorkerMessage>,
+    n_sender: event_log::Sender<StatusEvent>,
+    shutdown_rx: watch::Receiver<bool>,
+) -> Result<(SocketAddr, tokio::task::JoinHandle<()>), hyper::Error> {
+    let make_svc = make_service_fn(move |conn: &AddrStream| {
         let w_sender = w_sender.clone();
         let n_sub_factory = n_sender.clone();
+        let remote_addr = conn.remote_addr().to_string();
         async move {
             Ok::<_, Box<dyn Error + Send + Sync>>(service_fn(move |req: Request<Body>| {
-                http_handler(req, w_sender.clone(), n_sub_factory.clone())
+                http_handler(
+                    req,
+                    remote_addr.clone(),
+                    w_sender.clone(),
+                    n_sub_factory.clone(),
+                )
             }))
         }
     });
-    let server = Server::bind(&addr).serve(make_svc);
-    let info = json!({
-        "ip": server.local_addr().ip().to_string(),
-        "port": server.local_addr().port(),
-        "application_id": application_id,
-        "api_key": api_key
-    });
-    println!("{}", info.to_string());
-    let graceful = server.with_graceful_shutdown(async {
-        rx.await.ok();
+    let server = Server::try_bind(&addr)?.serve(make_svc);
+    let local_addr = server.local_addr();
+    let graceful = server.with_graceful_shutdown(wait_for_shutdown(shutdown_rx));
+    let task = tokio::spawn(async move {
+        graceful.await.expect("Some error on shutdown");
     });
-    graceful.await.expect("Some error on shutdown");
-    processing_task.await.unwrap();
+    Ok((local_addr, task))
+}
+
+async fn wait_for_shutdown(mut shutdown_rx: watch::Receiver<bool>) {
+    while !*shutdown_rx.borrow() {
+        if shutdown_rx.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn serve_pipe(
+    path: String,
+    mut pipe: NamedPipeServer,
+    w_sender: Sender<WorkerMessage>,
+    n_sender: event_log::Sender<StatusEvent>,
+    shutdown_rx: watch::Receiver<bool>,
+) -> std::io::Result<()> {
+    loop {
+        tokio::select! {
+            connected = pipe.connect() => connected?,
+            _ = wait_for_shutdown(shutdown_rx.clone()) => return Ok(()),
+        }
+        let stream = pipe;
+        pipe = ServerOptions::new().create(&path)?;
+        let w_sender = w_sender.clone();
+        let n_sender = n_sender.clone();
+        let remote_addr = path.clone();
+        let service = service_fn(move |req: Request<Body>| {
+            http_handler(req, remote_addr.clone(), w_sender.clone(), n_sender.clone())
+        });
+        let shutdown = wait_for_shutdown(shutdown_rx.clone());
+        tokio::spawn(async move {
+            let connection = Http::new().serve_connection(stream, service);
+            tokio::pin!(connection);
+            tokio::select! {
+                _ = connection.as_mut() => {}
+                _ = shutdown => {
+                    connection.as_mut().graceful_shutdown();
+                    connection.await.ok();
+                }
+            }
+        });
+    }
 }
 
 async fn notify(
-    req: Request<Body>,
+    body: &[u8],
+    audit: &mut AuditRecord,
     push_notification: Sender<WorkerMessage>,
 ) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
-    let buffer = hyper::body::aggregate(req).await?;
-    let request: NotificationRequest = serde_json::from_reader(Buf::reader(buffer))?;
+    let request: NotificationRequest = serde_json::from_slice(body)?;
+    let tag = request.tag.clone();
+    let group = request.group.clone();
+    let expires_at = request.expires_at;
+    let deliver_at = request.deliver_at;
     let content = get_notification_content(request);
     let config = NotificationConfig {
         content: content.expect("required field not defined"),
+        tag,
+        group,
+        expires_at,
+        deliver_at,
     };
     let (reply_sender, mut reply_receiver) = mpsc::channel(1);
     let message = WorkerMessage::CreateNotificationRequest(config, reply_sender);
@@ -404,6 +1097,7 @@ async fn notify(
     let id = reply_receiver.recv().await.unwrap();
     match id {
         Ok(id_value) => {
+            audit.notification_id = Some(id_value.to_string());
             let response_body = json!({
                 "id": id_value.to_string()
             });
@@ -437,24 +1131,62 @@ fn get_notification_content(request: NotificationRequest) -> Option<ToastContent
 
 async fn http_handler(
     req: Request<Body>,
+    remote_addr: String,
     notifications_pipe: Sender<WorkerMessage>,
-    s_sender: event_log::Sender<NotificationStatus>,
+    s_sender: event_log::Sender<StatusEvent>,
 ) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
-    if let false = is_authorized(&req) {
+    let mut audit = AuditRecord::new(remote_addr, req.method().as_str(), req.uri().path());
+    let result = route_request(req, &mut audit, notifications_pipe, s_sender).await;
+    match &result {
+        Ok(response) => audit.status = Some(response.status().as_u16()),
+        Err(e) => audit.error = Some(e.to_string()),
+    }
+    if let Ok(guard) = AUDIT_LOG.read() {
+        if let Some(Err(e)) = guard.as_ref().map(|log| log.write(&audit)) {
+            eprintln!("Failed to write audit log: {}", e);
+        }
+    }
+    result
+}
+
+async fn route_request(
+    req: Request<Body>,
+    audit: &mut AuditRecord,
+    notifications_pipe: Sender<WorkerMessage>,
+    s_sender: event_log::Sender<StatusEvent>,
+) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+    let (parts, body) = req.into_parts();
+    let body = match read_body(body, MAX_BODY_SIZE).await? {
+        Some(body) => body,
+        None => {
+            return Ok(Response::builder()
+                .status(StatusCode::PAYLOAD_TOO_LARGE)
+                .body(Body::empty())
+                .unwrap())
+        }
+    };
+    audit.set_content(&body);
+    audit.key = authorized_key(&parts, &body);
+    if audit.key.is_none() {
         Ok(Response::builder()
             .status(StatusCode::UNAUTHORIZED)
             .body(Body::empty())
             .unwrap())
     } else {
+        let req = Request::from_parts(parts, Body::empty());
         match (req.method(), req.uri().path()) {
             (&Method::GET, "/") => {
                 let response = Response::new(Body::from("POST /notification"));
                 Ok(response)
             }
-            (&Method::POST, "/notify") => notify(req, notifications_pipe).await,
+            (&Method::POST, "/notify") => notify(&body, audit, notifications_pipe).await,
             (&Method::GET, "/status-stream") => get_status(req, s_sender).await,
-            (&Method::DELETE, "/notification") => hide_notification(req, notifications_pipe).await,
+            (&Method::GET, "/events") => get_events(req, s_sender).await,
+            (&Method::DELETE, "/notification") => {
+                hide_notification(req, audit, notifications_pipe).await
+            }
             (&Method::DELETE, "/all") => hide_all_notification(notifications_pipe).await,
+            (&Method::POST, "/admin/rotate-key") => rotate_key(&body, audit).await,
             (_, "/quit") => match SHUTDOWN_TX.lock().await.take().map(|x| x.send(())) {
                 Some(Ok(_)) => Ok(Response::new(Body::from("Shutting down"))),
                 _ => Ok(Response::builder()
@@ -473,22 +1205,152 @@ async fn http_handler(
     }
 }
 
-fn is_authorized(req: &Request<Body>) -> bool {
-    if let Ok(guard) = API_KEY.read() {
-        if let Some(true) = req
-            .headers()
-            .get("Api-Key")
-            .zip(guard.as_deref())
-            .map(|(actual, expected)| actual.as_bytes().eq(expected))
-        {
-            return true;
+/// Reads whole request body, returns None when it is larger than limit.
+async fn read_body(mut body: Body, limit: usize) -> Result<Option<Bytes>, hyper::Error> {
+    let mut buffer: Vec<u8> = vec![];
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if buffer.len() + chunk.len() > limit {
+            return Ok(None);
         }
+        buffer.extend_from_slice(&chunk);
     }
-    false
+    Ok(Some(Bytes::from(buffer)))
+}
+
+fn current_key_identity() -> Option<String> {
+    API_KEY
+        .read()
+        .ok()
+        .and_then(|guard| guard.current_fingerprint())
+}
+
+/// Returns fingerprint of the key the request was authorized with.
+fn authorized_key(req: &request::Parts, body: &[u8]) -> Option<String> {
+    let mode = AUTH_MODE.read().map(|x| *x).unwrap_or(AuthMode::ApiKey);
+    let guard = API_KEY.read().ok()?;
+    let now = Instant::now();
+    if mode != AuthMode::Signature {
+        if let Some(actual) = req.headers.get("Api-Key") {
+            if let Some(key) = guard
+                .valid_keys(now)
+                .find(|expected| actual.as_bytes().eq(*expected))
+            {
+                return Some(audit_log::key_fingerprint(key));
+            }
+        }
+    }
+    if mode != AuthMode::ApiKey {
+        let header = |name: &str| req.headers.get(name).and_then(|x| x.to_str().ok());
+        let timestamp = header(signature::TIMESTAMP_HEADER);
+        let request_signature = header(signature::SIGNATURE_HEADER);
+        if let (Some(timestamp), Some(request_signature)) = (timestamp, request_signature) {
+            let path = req
+                .uri
+                .path_and_query()
+                .map(|x| x.as_str())
+                .unwrap_or("/");
+            let verifier = SIGNATURE_VERIFIER.read().ok()?;
+            let mut error = None;
+            for secret in guard.valid_keys(now) {
+                match verifier.verify(
+                    secret,
+                    req.method.as_str(),
+                    path,
+                    timestamp,
+                    request_signature,
+                    body,
+                    chrono::Utc::now().timestamp(),
+                ) {
+                    Ok(_) => return Some(audit_log::key_fingerprint(secret)),
+                    Err(e) => error = Some(e),
+                }
+            }
+            if let Some(e) = error {
+                eprintln!("Rejected signed request: {}", e);
+            }
+        }
+    }
+    None
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RotateKeyRequest {
+    #[serde(default)]
+    api_key: Option<String>,
+    #[serde(default)]
+    grace_secs: Option<u64>,
+}
+
+/// Rotates the api key, only requests authorized with the current key may rotate it.
+async fn rotate_key(
+    body: &[u8],
+    audit: &AuditRecord,
+) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+    let is_current = |fingerprint: &String| {
+        API_KEY
+            .read()
+            .map(|guard| guard.is_current(fingerprint))
+            .unwrap_or(false)
+    };
+    if !audit.key.as_ref().is_some_and(is_current) {
+        return Ok(Response::builder()
+            .status(StatusCode::FORBIDDEN)
+            .body(Body::from("Only the current api key can rotate keys"))
+            .unwrap());
+    }
+    let request: RotateKeyRequest = if body.is_empty() {
+        RotateKeyRequest::default()
+    } else {
+        serde_json::from_slice(body)?
+    };
+    let api_key = request
+        .api_key
+        .filter(|x| !x.trim().is_empty())
+        .unwrap_or_else(|| utils::get_random_string(50));
+    let key_file = API_KEY_FILE.read().map(|x| x.clone()).unwrap_or_default();
+    if let Some(path) = &key_file {
+        if let Err(e) = utils::write_user_only_file(path, &api_key) {
+            return Ok(Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from(format!("Could not write api key file: {}", e)))
+                .unwrap());
+        }
+    }
+    let grace = match API_KEY.write() {
+        Ok(mut guard) => {
+            let grace = request
+                .grace_secs
+                .map(Duration::from_secs)
+                .unwrap_or(guard.default_grace);
+            guard.rotate(&api_key, grace, Instant::now());
+            grace
+        }
+        Err(_) => {
+            return Ok(Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::empty())
+                .unwrap())
+        }
+    };
+    let mut response_body = json!({
+        "api_key_fingerprint": audit_log::key_fingerprint(api_key.as_bytes()),
+        "grace_secs": grace.as_secs(),
+    });
+    match key_file {
+        Some(path) => response_body["api_key_file"] = json!(path),
+        None => response_body["api_key"] = json!(api_key),
+    }
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(response_body.to_string()))
+        .unwrap())
 }
 
 async fn hide_notification(
     req: Request<Body>,
+    audit: &mut AuditRecord,
     notifications_pipe: Sender<WorkerMessage>,
 ) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
     if let Some(q) = req.uri().query() {
@@ -497,6 +1359,7 @@ async fn hide_notification(
             .collect::<HashMap<String, String>>();
         if let Some(id_str) = params.get("id") {
             if let Ok(id) = Uuid::parse_str(id_str) {
+                audit.notification_id = Some(id.to_string());
                 return Ok(send_worker_request(notifications_pipe, |reply| {
                     WorkerMessage::HideNotificationRequest(id, reply)
                 })
@@ -519,77 +1382,253 @@ async fn hide_all_notification(
         .await)
 }
 
+/// Filters shared by `/status-stream` and `/events`.
+struct EventsQuery {
+    from: usize,
+    tail: Option<usize>,
+    since: Option<DateTime<Utc>>,
+    /// Whether `from` or `tail` was specified, so lost events should be reported.
+    report_gap: bool,
+    params: HashMap<String, String>,
+}
+
+impl EventsQuery {
+    fn parse(req: &Request<Body>) -> Result<EventsQuery, String> {
+        let params = req
+            .uri()
+            .query()
+            .map(|q| {
+                form_urlencoded::parse(q.as_bytes())
+                    .into_owned()
+                    .collect::<HashMap<String, String>>()
+            })
+            .unwrap_or_default();
+        let from = params
+            .get("from")
+            .and_then(|id| atoi::<usize>(id.as_bytes()))
+            .unwrap_or(0);
+        let tail = params
+            .get("tail")
+            .and_then(|count| atoi::<usize>(count.as_bytes()));
+        let since = match params.get("since").map(|x| DateTime::parse_from_rfc3339(x)) {
+            None => None,
+            Some(Ok(since)) => Some(since.with_timezone(&Utc)),
+            Some(Err(e)) => return Err(format!("Invalid since: {}", e)),
+        };
+        Ok(EventsQuery {
+            from,
+            tail,
+            since,
+            report_gap: tail.is_some() || params.contains_key("from"),
+            params,
+        })
+    }
+
+    fn start(&self) -> StartAt {
+        match self.tail {
+            Some(count) => StartAt::Tail(count),
+            None => StartAt::Number(self.from),
+        }
+    }
+
+    fn matches(&self, event: &StatusEvent) -> bool {
+        match self.since {
+            Some(since) => event.timestamp >= since,
+            None => true,
+        }
+    }
+}
+
+async fn get_events(
+    req: Request<Body>,
+    s_sender: event_log::Sender<StatusEvent>,
+) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+    let query = match EventsQuery::parse(&req) {
+        Ok(query) => query,
+        Err(e) => {
+            return Ok(Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header(header::CONTENT_TYPE, "text/plain")
+                .body(Body::from(e))
+                .unwrap());
+        }
+    };
+    let to = query.params.get("to").and_then(|x| atoi::<usize>(x.as_bytes()));
+    let limit = query
+        .params
+        .get("limit")
+        .and_then(|x| atoi::<usize>(x.as_bytes()))
+        .unwrap_or(DEFAULT_EVENTS_PAGE)
+        .clamp(1, MAX_EVENTS_PAGE);
+    let ndjson = query.params.get("format").map(|x| x.as_str()) == Some("ndjson")
+        || req
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|x| x.to_str().ok())
+            .is_some_and(|x| x.contains("application/x-ndjson"));
+    let page = s_sender.read_range(query.start(), to, limit, |event| query.matches(event));
+    let from = match query.start() {
+        StartAt::Number(from) => from,
+        StartAt::Tail(_) => page.first_available,
+    };
+    let gap = (query.report_gap && page.first_available > from).then(|| {
+        json!({
+            "firstAvailable": page.first_available,
+            "missed": page.first_available - from,
+            "type": "Gap"
+        })
+    });
+    let events = page
+        .events
+        .iter()
+        .map(|(number, event)| model::stream_message(*number, event));
+    let (content_type, body) = if ndjson {
+        let mut lines = gap.into_iter().chain(events).collect::<Vec<_>>();
+        lines.push(json!({ "next": page.next, "type": "Cursor" }));
+        let body = lines
+            .iter()
+            .map(|line| line.to_string() + "\n")
+            .collect::<String>();
+        ("application/x-ndjson", body)
+    } else {
+        let body = json!({
+            "events": events.collect::<Vec<_>>(),
+            "firstAvailable": page.first_available,
+            "next": page.next,
+        });
+        ("application/json", body.to_string())
+    };
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header("X-Next-Cursor", page.next.to_string())
+        .body(Body::from(body))
+        .unwrap())
+}
+
 async fn get_status(
     _req: Request<Body>,
-    s_sender: event_log::Sender<NotificationStatus>,
+    s_sender: event_log::Sender<StatusEvent>,
 ) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
-    let last_number = _req
-        .uri()
-        .query()
-        .map(|q| {
-            form_urlencoded::parse(q.as_bytes())
-                .into_owned()
-                .collect::<HashMap<String, String>>()
-        })
-        .and_then(|h| h.get("from").map(|x| x.to_string()))
-        .and_then(|id| atoi::<usize>(id.as_bytes()))
-        .unwrap_or(0);
+    let query = match EventsQuery::parse(&_req) {
+        Ok(query) => query,
+        Err(e) => {
+            return Ok(Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header(header::CONTENT_TYPE, "text/plain")
+                .body(Body::from(e))
+                .unwrap());
+        }
+    };
+
+    let config = STATUS_STREAM.read().map(|x| *x).unwrap_or_default();
+    let slot = match StatusStreamSlot::acquire(config.max_subscribers) {
+        Some(slot) => slot,
+        None => {
+            return Ok(Response::builder()
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .header(header::CONTENT_TYPE, "text/plain")
+                .body(Body::from("Too many status-stream clients"))
+                .unwrap());
+        }
+    };
 
     let (mut body_tx, body) = Body::channel();
-    let mut subscriber = s_sender.subscribe().await;
+    let mut subscriber = match query.tail {
+        Some(count) => s_sender.subscribe_tail(count).await,
+        None => s_sender.subscribe_from(query.from).await,
+    };
+    let mut heartbeat = config
+        .heartbeat
+        .map(|period| tokio::time::interval_at(tokio::time::Instant::now() + period, period));
+    let shutdown_rx = SHUTDOWN_SIGNAL.subscribe();
     tokio::spawn(async move {
+        let _slot = slot;
         loop {
-            match subscriber.recv().await {
-                Some((num, status)) => {
-                    if last_number > num {
-                        continue;
-                    }
-                    let message: String = match status {
-                        NotificationStatus::Activated(id, info) => json!({
-                            "number": num,
-                            "id": id,
-                            "info": info,
-                            "type": "Activated"
-                        })
-                            .to_string(),
-                        NotificationStatus::Dismissed(id, reason) => json!({
-                            "number": num,
-                            "id": id,
-                            "dismissReason": reason,
-                            "type": "Dismissed"
-                        })
-                            .to_string(),
-                        NotificationStatus::DismissedError(id, msg) => json!({
-                            "number": num,
-                            "id": id,
-                            "description": msg,
-                            "type": "DismissedError"
-                        })
-                            .to_string(),
-                        NotificationStatus::Failed(id, msg) => json!({
-                            "number": num,
-                            "id": id,
-                            "description": msg,
-                            "type": "Failed"
-                        })
-                            .to_string(),
-                    };
+            let item = tokio::select! {
+                item = subscriber.recv() => item,
+                _ = next_heartbeat(&mut heartbeat) => {
+                    let message = json!({
+                        "timestamp": Utc::now(),
+                        "type": "Heartbeat"
+                    });
                     if body_tx
-                        .send_data(hyper::body::Bytes::from(message + "\n"))
+                        .send_data(hyper::body::Bytes::from(message.to_string() + "\n"))
                         .await
                         .is_err()
                     {
-                        subscriber.drop_async().await;
                         break;
                     }
+                    continue;
                 }
-                _ => break,
+                _ = wait_for_shutdown(shutdown_rx.clone()) => break,
+            };
+            let message: String = match item {
+                Some(LogItem::Lagged(missed)) => json!({
+                    "missed": missed,
+                    "type": "Lagged"
+                })
+                    .to_string(),
+                Some(LogItem::Gap { .. }) if !query.report_gap => continue,
+                Some(LogItem::Gap {
+                    first_available,
+                    missed,
+                }) => json!({
+                    "firstAvailable": first_available,
+                    "missed": missed,
+                    "type": "Gap"
+                })
+                    .to_string(),
+                Some(LogItem::Event(num, event)) => {
+                    if (query.tail.is_none() && query.from > num) || !query.matches(&event) {
+                        continue;
+                    }
+                    model::stream_message(num, &event).to_string()
+                }
+                None => {
+                    if subscriber.is_lag_disconnected() {
+                        eprintln!("Disconnected lagging status-stream subscriber");
+                    }
+                    break;
+                }
+            };
+            if body_tx
+                .send_data(hyper::body::Bytes::from(message + "\n"))
+                .await
+                .is_err()
+            {
+                subscriber.drop_async().await;
+                break;
+            }
+            if let Some(heartbeat) = heartbeat.as_mut() {
+                heartbeat.reset();
             }
         }
     });
     Ok(Response::new(body))
 }
 
+async fn next_heartbeat(heartbeat: &mut Option<tokio::time::Interval>) {
+    match heartbeat {
+        Some(heartbeat) => {
+            heartbeat.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Completes when the earliest remembered notification expires. The wait is limited to a minute
+/// so that changes of the system clock are noticed.
+async fn next_expiration(notifier: &Notifier) {
+    match notifier.next_expiration() {
+        Some(expires_at) => {
+            let wait = (expires_at - Utc::now()).to_std().unwrap_or_default();
+            sleep(wait.min(Duration::from_secs(60))).await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
 async fn send_worker_request<TMessage, Factory>(
     worker_pipe: Sender<TMessage>,
     f: Factory,
@@ -621,7 +1660,17 @@ async fn process_notification_api_messages(
     mut notifier: Notifier,
     mut receiver: Receiver<WorkerMessage>,
 ) {
-    while let Some(received_message) = receiver.recv().await {
+    loop {
+        let received_message = tokio::select! {
+            received_message = receiver.recv() => received_message,
+            _ = next_expiration(&notifier) => {
+                notifier.remove_expired();
+                continue;
+            }
+        };
+        let Some(received_message) = received_message else {
+            break;
+        };
         match received_message {
             WorkerMessage::CreateNotificationRequest(config, respond) => {
                 let id = notifier.notify(config);
@@ -1,4 +1,5 @@
-use crate::{event_log, DismissReason, NotificationActivationInfo, NotificationStatus};
+use crate::xml_policy::XmlPathPolicy;
+use chrono::{DateTime, Utc};
 use rand::Rng;
 use std::collections::HashMap;
 use std::fs;
@@ -7,8 +8,16 @@ use std::ops::Deref;
 use std::sync::Arc;
 use tokio::sync::mpsc::Sender;
 use uuid::Uuid;
-use windows::Foundation::IReference;
-use windows::UI::Notifications::{ToastDismissalReason, ToastNotifier};
+use win_toast_core::event_log;
+use win_toast_core::model::{
+    DismissReason, NotificationActivationInfo, NotificationStatus, StatusEvent,
+};
+use windows::Foundation::{DateTime as WinDateTime, IReference, PropertyValue};
+use windows::UI::Notifications::{
+    ScheduledToastNotification, ToastDismissalReason, ToastNotifier,
+};
+#[cfg(test)]
+use windows::Win32::Foundation::E_FAIL;
 use windows::{
     core::{ComInterface, IInspectable, HSTRING},
     Data::Xml::Dom::XmlDocument,
@@ -28,36 +37,67 @@ pub enum ToastContent {
 #[derive(Debug, Clone)]
 pub struct NotificationConfig {
     pub content: ToastContent,
+    /// Tag and group of the toast, a toast with the same ones is replaced.
+    pub tag: Option<String>,
+    pub group: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Time to show the toast at instead of showing it immediately.
+    pub deliver_at: Option<DateTime<Utc>>,
+}
+
+impl NotificationConfig {
+    pub fn new(content: ToastContent) -> NotificationConfig {
+        NotificationConfig {
+            content,
+            tag: None,
+            group: None,
+            expires_at: None,
+            deliver_at: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Toast {
+    Shown(Box<ToastNotification>),
+    Scheduled(Box<ScheduledToastNotification>),
 }
 
 #[derive(Debug, Clone)]
 pub struct Notification {
     id: Uuid,
     config: NotificationConfig,
-    toast: Option<Box<ToastNotification>>,
+    toast: Option<Toast>,
+    created_at: DateTime<Utc>,
 }
 
 pub struct Notifier {
+    application_id: String,
     notifications: HashMap<Uuid, Notification>,
     notifier: ToastNotifier,
-    status_writer: event_log::Sender<NotificationStatus>,
+    status_writer: event_log::Sender<StatusEvent>,
+    xml_policy: XmlPathPolicy,
 }
 
 impl Notifier {
     pub fn new(
         application_id: &String,
-        s_sender: event_log::Sender<NotificationStatus>,
+        s_sender: event_log::Sender<StatusEvent>,
+        xml_policy: XmlPathPolicy,
     ) -> Result<Notifier, String> {
         match ToastNotificationManager::CreateToastNotifierWithId(&hs(application_id)) {
             Ok(notifier) => Ok(Notifier {
+                application_id: application_id.to_string(),
                 notifications: HashMap::new(),
                 notifier,
                 status_writer: s_sender,
+                xml_policy,
             }),
             Err(e) => Err(e.message().to_string_lossy()),
         }
     }
     pub(crate) fn notify(&mut self, config: NotificationConfig) -> Result<Uuid, String> {
+        self.remove_expired();
         let mut id: Uuid = Uuid::new_v4();
         while self.notifications.contains_key(&id) {
             id = Uuid::new_v4();
@@ -66,16 +106,29 @@ impl Notifier {
             id,
             config,
             toast: None,
+            created_at: Utc::now(),
         };
         let raw_content = match &notification.config.content {
             ToastContent::Raw(raw) => Ok(String::from(raw)),
-            ToastContent::Path(path) => {
-                fs::read_to_string(path).map_err(|x| format!("{}. path={}", x.to_string(), path))
-            }
+            ToastContent::Path(path) => self.xml_policy.read(path),
         };
         match raw_content {
             Ok(content) => match self.display_notification(&mut notification, content) {
                 Ok(_) => {
+                    let status = match notification.config.deliver_at {
+                        Some(deliver_at) => NotificationStatus::Scheduled(id.to_string(), deliver_at),
+                        None => NotificationStatus::Shown(id.to_string()),
+                    };
+                    self.emit(&notification, status);
+                    if let Some(replaced) = self.find_replaced(&notification) {
+                        self.notifications.remove(&replaced.id);
+                        if let Some(Toast::Scheduled(toast)) = &replaced.toast {
+                            self.notifier.RemoveFromSchedule(toast.deref()).ok();
+                        }
+                        let status =
+                            NotificationStatus::Replaced(replaced.id.to_string(), id.to_string());
+                        self.emit(&replaced, status);
+                    }
                     self.notifications.insert(notification.id, notification);
                     Ok(id)
                 }
@@ -87,15 +140,17 @@ impl Notifier {
             Err(msg) => Err(msg),
         }
     }
+    /// Hides all notifications and forgets them also when some could not be hidden, the first
+    /// error is returned.
     pub(crate) fn hide_all(&mut self) -> Result<(), String> {
-        for notification in self.notifications.values().into_iter() {
-            self.hide(&notification)?;
-        }
+        self.remove_expired();
+        let result = hide_each(self.notifications.values(), |n| self.hide(n));
         self.notifications.clear();
-        Ok(())
+        result
     }
 
     pub fn hide_by_id(&mut self, id: Uuid) -> Result<(), String> {
+        self.remove_expired();
         match self.notifications.remove(&id) {
             None => Err("Not found".to_string()),
             Some(notification) => {
@@ -106,18 +161,80 @@ impl Notifier {
     }
 
     fn hide(&self, notification: &Notification) -> Result<(), String> {
-        match notification.toast.as_ref() {
-            None => Err("Toast not defined".to_string()),
-            Some(toast) => {
-                let toast = toast.deref();
-                match self.notifier.Hide(toast) {
-                    Ok(_) => Ok(()),
-                    Err(e) => Err(e.message().to_string_lossy()),
-                }
+        let hidden = match notification.toast.as_ref() {
+            None => return Err("Toast not defined".to_string()),
+            Some(Toast::Shown(toast)) => self.notifier.Hide(toast.deref()),
+            Some(Toast::Scheduled(toast)) => remove_scheduled(
+                || self.notifier.RemoveFromSchedule(toast.deref()),
+                || {
+                    ToastNotificationManager::History()?.RemoveGroupedTagWithId(
+                        &toast.Tag()?,
+                        &toast.Group()?,
+                        &hs(&self.application_id),
+                    )
+                },
+            ),
+        };
+        match hidden {
+            Ok(_) => {
+                let status = NotificationStatus::HiddenByApi(notification.id.to_string());
+                self.emit(notification, status);
+                Ok(())
             }
+            Err(e) => Err(e.message().to_string_lossy()),
         }
     }
 
+    /// Forgets notifications whose expiration time passed, their events are stamped with it.
+    pub(crate) fn remove_expired(&mut self) {
+        let now = Utc::now();
+        let expired: Vec<(Uuid, DateTime<Utc>)> = self
+            .notifications
+            .values()
+            .filter_map(|n| n.config.expires_at.map(|expires_at| (n.id, expires_at)))
+            .filter(|(_, expires_at)| *expires_at <= now)
+            .collect();
+        for (id, expires_at) in expired {
+            if let Some(notification) = self.notifications.remove(&id) {
+                let mut event =
+                    self.event(&notification, NotificationStatus::Expired(id.to_string()));
+                event.timestamp = expires_at;
+                self.status_writer.blocking_send(event).ok();
+            }
+        }
+    }
+
+    /// Earliest expiration time of the remembered notifications.
+    pub(crate) fn next_expiration(&self) -> Option<DateTime<Utc>> {
+        self.notifications
+            .values()
+            .filter_map(|n| n.config.expires_at)
+            .min()
+    }
+
+    /// Notification which Windows replaces with the given one because of the same tag and group.
+    fn find_replaced(&self, notification: &Notification) -> Option<Notification> {
+        let tag = notification.config.tag.as_ref()?;
+        self.notifications
+            .values()
+            .find(|n| {
+                n.id != notification.id
+                    && n.config.tag.as_ref() == Some(tag)
+                    && n.config.group == notification.config.group
+            })
+            .cloned()
+    }
+
+    fn emit(&self, notification: &Notification, status: NotificationStatus) {
+        self.status_writer
+            .blocking_send(self.event(notification, status))
+            .ok();
+    }
+
+    fn event(&self, notification: &Notification, status: NotificationStatus) -> StatusEvent {
+        StatusEvent::new(status, &self.application_id, notification.created_at)
+    }
+
     fn display_notification(
         &mut self,
         notification: &mut Notification,
@@ -125,11 +242,41 @@ impl Notifier {
     ) -> windows::core::Result<()> {
         let toast_doc = XmlDocument::new()?;
         let _ = &toast_doc.LoadXml(&hs(raw_content))?;
+        let config = &notification.config;
+        if let Some(deliver_at) = config.deliver_at {
+            let toast = ScheduledToastNotification::CreateScheduledToastNotification(
+                &toast_doc,
+                win_date_time(deliver_at),
+            )?;
+            // Delivered toasts are removed from the action center by tag, so one is always set.
+            let tag = config.tag.clone().unwrap_or(notification.id.to_string());
+            toast.SetTag(&hs(tag))?;
+            if let Some(group) = &config.group {
+                toast.SetGroup(&hs(group))?;
+            }
+            if let Some(expires_at) = config.expires_at {
+                toast.SetExpirationTime(&win_date_time_reference(expires_at)?)?;
+            }
+            self.notifier.AddToSchedule(&toast)?;
+            notification.toast = Some(Toast::Scheduled(Box::new(toast)));
+            return Ok(());
+        }
         let toast = ToastNotification::CreateToastNotification(&toast_doc)?;
         toast.SetExpiresOnReboot(true)?;
+        if let Some(tag) = &config.tag {
+            toast.SetTag(&hs(tag))?;
+        }
+        if let Some(group) = &config.group {
+            toast.SetGroup(&hs(group))?;
+        }
+        if let Some(expires_at) = config.expires_at {
+            toast.SetExpirationTime(&win_date_time_reference(expires_at)?)?;
+        }
         let _ = &self.notifier.Show(&toast)?;
         let a_status_writer = self.status_writer.clone();
         let notification_id = notification.id;
+        let created_at = notification.created_at;
+        let a_application_id = self.application_id.clone();
         toast.Activated(&TypedEventHandler::new(
             move |_, args: &Option<IInspectable>| {
                 let args = args
@@ -150,12 +297,14 @@ impl Notifier {
                     }
                     let info = NotificationActivationInfo { arguments, inputs };
                     let status = NotificationStatus::Activated(notification_id.to_string(), info);
-                    a_status_writer.blocking_send(status).ok();
+                    let event = StatusEvent::new(status, &a_application_id, created_at);
+                    a_status_writer.blocking_send(event).ok();
                 }
                 Ok(())
             },
         ))?;
         let d_status_writer = self.status_writer.clone();
+        let d_application_id = self.application_id.clone();
         toast.Dismissed(&TypedEventHandler::new(
             move |_, args: &Option<ToastDismissedEventArgs>| {
                 if let Some(args) = args {
@@ -175,12 +324,14 @@ impl Notifier {
                             e.message().to_string(),
                         ),
                     };
-                    d_status_writer.blocking_send(status).ok();
+                    let event = StatusEvent::new(status, &d_application_id, created_at);
+                    d_status_writer.blocking_send(event).ok();
                 }
                 Ok(())
             },
         ))?;
         let f_status_writer = self.status_writer.clone();
+        let f_application_id = self.application_id.clone();
         toast.Failed(&TypedEventHandler::new(
             move |_, args: &Option<ToastFailedEventArgs>| {
                 if let Some(args) = args {
@@ -190,17 +341,86 @@ impl Notifier {
                             notification_id.to_string(),
                             e.message().to_string(),
                         );
-                        f_status_writer.blocking_send(status).ok();
+                        let event = StatusEvent::new(status, &f_application_id, created_at);
+                        f_status_writer.blocking_send(event).ok();
                     }
                 }
                 Ok(())
             },
         ))?;
-        notification.toast = Some(Box::new(toast));
+        notification.toast = Some(Toast::Shown(Box::new(toast)));
         Ok(())
     }
 }
 
+/// Calls `hide` for all notifications, the first error is returned after all of them were tried.
+fn hide_each<'a>(
+    notifications: impl Iterator<Item = &'a Notification>,
+    mut hide: impl FnMut(&Notification) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut result = Ok(());
+    for notification in notifications {
+        if let Err(e) = hide(notification) {
+            result = result.and(Err(e));
+        }
+    }
+    result
+}
+
+/// Removes a toast from the schedule. A toast which was delivered already is not scheduled
+/// anymore, it is removed from the action center instead.
+fn remove_scheduled(
+    remove_from_schedule: impl FnOnce() -> windows::core::Result<()>,
+    remove_from_history: impl FnOnce() -> windows::core::Result<()>,
+) -> windows::core::Result<()> {
+    remove_from_schedule().or_else(|_| remove_from_history())
+}
+
+/// Converts to Windows time, 100 nanosecond intervals since 1601-01-01.
+fn win_date_time(time: DateTime<Utc>) -> WinDateTime {
+    const UNIX_EPOCH_INTERVALS: i64 = 116_444_736_000_000_000;
+    WinDateTime {
+        UniversalTime: UNIX_EPOCH_INTERVALS
+            + time.timestamp() * 10_000_000
+            + time.timestamp_subsec_nanos() as i64 / 100,
+    }
+}
+
+fn win_date_time_reference(time: DateTime<Utc>) -> windows::core::Result<IReference<WinDateTime>> {
+    PropertyValue::CreateDateTime(win_date_time(time))?.cast::<IReference<WinDateTime>>()
+}
+
 pub(crate) fn hs(s: impl AsRef<str>) -> HSTRING {
     HSTRING::from(s.as_ref())
 }
+
+#[test]
+fn hide_delivered_scheduled_test() {
+    let not_scheduled = || Err(windows::core::Error::new(E_FAIL, hs("Not scheduled")));
+    let mut removed = false;
+    let result = remove_scheduled(not_scheduled, || {
+        removed = true;
+        Ok(())
+    });
+    assert!(result.is_ok());
+    assert!(removed);
+    assert!(remove_scheduled(not_scheduled, not_scheduled).is_err());
+    let notifications: Vec<Notification> = (0..3)
+        .map(|_| Notification {
+            id: Uuid::new_v4(),
+            config: NotificationConfig::new(ToastContent::Raw(String::new())),
+            toast: None,
+            created_at: Utc::now(),
+        })
+        .collect();
+    let mut tried = vec![];
+    let result = hide_each(notifications.iter(), |n| {
+        tried.push(n.id);
+        match tried.len() {
+            1 => Ok(()),
+            count => Err(format!("Failed {}", count)),
+        }
+    });
+    assert_eq!(result, Err("Failed 2".to_string()));
+    assert_eq!(tried.len(), 3);
+}
@@ -1,4 +1,4 @@
-use crate::{elevator, println_pipe, utils};
+use crate::{artifacts, elevator, println_pipe, utils};
 use clap::builder::Str;
 use serde::de::Error;
 use std::env;
@@ -6,33 +6,35 @@ use std::env::current_exe;
 use std::ffi::OsStr;
 use std::fmt::{format, Debug, Formatter, Pointer};
 use std::fs::metadata;
+use std::io::ErrorKind;
 use std::os::windows::ffi::OsStrExt;
-use std::path::PathBuf;
-use winreg::enums::{HKEY_CLASSES_ROOT, KEY_ALL_ACCESS, KEY_SET_VALUE};
+use std::path::{Path, PathBuf};
+use win_toast_core::artifacts::Artifact;
+use win_toast_core::icon::{self, IconFormat};
+use win_toast_core::inventory::{self, RegisteredApp, Shortcut};
+use win_toast_core::lnk::{self, ShellLink};
+use win_toast_core::manifest::{self, Manifest};
+use win_toast_core::registration::{self, AppRegistration, Change, Plan, Scope};
+use win_toast_core::registry::{RegValue, Registry};
+use winreg::enums::{
+    RegType, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, KEY_ALL_ACCESS, KEY_SET_VALUE,
+};
+use winreg::types::FromRegValue;
 use winreg::RegKey;
 
-pub async fn run_elevated(
-    command: &str,
-    app_id: String,
-    display_name: Option<String>,
-    icon_uri: Option<String>,
-) -> Result<(), String> {
+/// Runs the command as admin, `args` are quoted and passed after the pipe name.
+pub async fn run_elevated(command: &str, args: Vec<String>) -> Result<(), String> {
     let pipe_name = utils::get_random_string(20);
-    let mut args = String::from(format!(
-        "{} -a \"{}\" -p \"{}\"",
-        command, app_id, pipe_name
-    ));
-    if let Some(name) = display_name {
-        args.push_str(format!(" -n \"{}\"", name).as_str());
-    }
-    if let Some(icon) = icon_uri {
-        args.push_str(format!(" -i \"{}\"", icon).as_str());
+    let mut command_line = format!("{} -p {}", command, utils::quote_arg(&pipe_name));
+    for arg in args {
+        command_line.push(' ');
+        command_line.push_str(&utils::quote_arg(&arg));
     }
     let exe_path = env::current_exe()
         .expect("Failed to get current executable path")
         .display()
         .to_string();
-    elevator::elevate(exe_path, args, pipe_name).await
+    elevator::elevate(exe_path, command_line, pipe_name).await
 }
 
 #[derive(Debug)]
@@ -41,130 +43,471 @@ pub enum RegistrationError {
     ArgumentError(String),
 }
 
-pub fn unregister_app_id(app_id: String) -> Result<(), RegistrationError> {
-    if app_id.contains(r"\") || app_id.contains("/") {
-        return Err(RegistrationError::ArgumentError(format!(
-            "app id [{}] contains invalid characters",
-            app_id
-        )));
+/// System registry below the classes key of a scope.
+pub struct WinRegistry {
+    scope: Scope,
+    hive: RegKey,
+    /// Path of the classes key in the hive, keys are opened from the hive so that reading
+    /// does not need write access to the classes key.
+    base: &'static str,
+    name: String,
+}
+
+impl WinRegistry {
+    pub fn for_scope(scope: Scope) -> WinRegistry {
+        let (_, base) = scope.classes_key();
+        let hive = match scope {
+            Scope::User => HKEY_CURRENT_USER,
+            Scope::Machine => HKEY_LOCAL_MACHINE,
+        };
+        WinRegistry {
+            scope,
+            hive: RegKey::predef(hive),
+            base,
+            name: scope.root_name(),
+        }
+    }
+
+    fn path(&self, key: &str) -> String {
+        format!(r"{}\{}", self.base, key)
     }
-    let classes_root = RegKey::predef(HKEY_CLASSES_ROOT);
-    if let Ok(key) = classes_root.open_subkey_with_flags("AppUserModelId", KEY_ALL_ACCESS) {
-        key.delete_subkey(&app_id)
-            .map_err(|e| RegistrationError::FileError(e, app_id.to_string()))?;
-        println_pipe!("Unregistered");
+
+    fn open(&self, key: &str) -> std::io::Result<Option<RegKey>> {
+        match self.hive.open_subkey(self.path(key)) {
+            Ok(key) => Ok(Some(key)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
     }
-    Ok(())
 }
 
-pub fn register_app_id(
-    app_id: String,
-    display_name: Option<String>,
-    icon_path: Option<String>,
-) -> Result<(), RegistrationError> {
-    if app_id.contains(r"\") || app_id.contains("/") {
-        return Err(RegistrationError::ArgumentError(format!(
-            "app id [{}] contains invalid characters",
-            app_id
-        )));
-    }
-    if let Some(icon) = &icon_path {
-        let path = PathBuf::from(icon);
-        let extension_error: String = "Icon path should have png extension.".into();
-        match path
-            .extension()
-            .ok_or(RegistrationError::ArgumentError(extension_error.clone()))?
-        {
-            ext => {
-                if !ext.eq("png") {
-                    return Err(RegistrationError::ArgumentError(extension_error));
-                }
-            }
+fn from_raw_value(raw: &winreg::RegValue) -> RegValue {
+    match raw.vtype {
+        RegType::REG_DWORD => RegValue::Dword(u32::from_reg_value(raw).unwrap_or_default()),
+        _ => RegValue::String(String::from_reg_value(raw).unwrap_or_default()),
+    }
+}
+
+impl Registry for WinRegistry {
+    fn root_name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn key_exists(&self, key: &str) -> std::io::Result<bool> {
+        Ok(self.open(key)?.is_some())
+    }
+
+    fn get_value(&self, key: &str, name: &str) -> std::io::Result<Option<RegValue>> {
+        let Some(key) = self.open(key)? else {
+            return Ok(None);
+        };
+        match key.get_raw_value(name) {
+            Ok(raw) => Ok(Some(from_raw_value(&raw))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
         }
     }
-    let classes_root = RegKey::predef(HKEY_CLASSES_ROOT);
-    let app_user_model_id = match classes_root.open_subkey("AppUserModelId") {
-        Ok(key) => key,
-        Err(_) => {
-            let (key, _) = classes_root
-                .create_subkey("AppUserModelId")
-                .map_err(|e| RegistrationError::FileError(e, "AppUserModelId".to_string()))?;
-            key
+
+    fn values(&self, key: &str) -> std::io::Result<Vec<(String, RegValue)>> {
+        let Some(key) = self.open(key)? else {
+            return Ok(vec![]);
+        };
+        key.enum_values()
+            .map(|value| value.map(|(name, raw)| (name, from_raw_value(&raw))))
+            .collect()
+    }
+
+    fn subkeys(&self, key: &str) -> std::io::Result<Vec<String>> {
+        let Some(key) = self.open(key)? else {
+            return Ok(vec![]);
+        };
+        key.enum_keys().collect()
+    }
+
+    fn create_key(&mut self, key: &str) -> std::io::Result<()> {
+        self.hive.create_subkey(self.path(key)).map(|_| ())
+    }
+
+    fn set_value(&mut self, key: &str, name: &str, value: &RegValue) -> std::io::Result<()> {
+        let key = self
+            .hive
+            .open_subkey_with_flags(self.path(key), KEY_SET_VALUE)?;
+        match value {
+            RegValue::String(value) => key.set_value(name, value),
+            RegValue::Dword(value) => key.set_value(name, value),
         }
-    };
-    let app_id_sub_key = match app_user_model_id.open_subkey_with_flags(&app_id, KEY_SET_VALUE) {
-        Ok(key) => key,
-        Err(_) => {
-            let (app_id, _) = app_user_model_id
-                .create_subkey(&app_id)
-                .map_err(|e| RegistrationError::FileError(e, app_id.to_string()))?;
-            app_id
+    }
+
+    fn delete_value(&mut self, key: &str, name: &str) -> std::io::Result<()> {
+        self.hive
+            .open_subkey_with_flags(self.path(key), KEY_SET_VALUE)?
+            .delete_value(name)
+    }
+
+    fn delete_key(&mut self, key: &str) -> std::io::Result<()> {
+        self.hive.delete_subkey_all(self.path(key))
+    }
+}
+
+/// Prints the plan with `dry_run`. Otherwise records the artifacts it creates, writes converted
+/// `icons`, applies registry changes, deletes files of the plan and forgets deleted artifacts.
+fn apply_plan(
+    registry: &mut WinRegistry,
+    plan: Plan,
+    icons: &[ConvertedIcon],
+    dry_run: bool,
+) -> Result<(), RegistrationError> {
+    if dry_run {
+        print!("{}", plan);
+        return Ok(());
+    }
+    let scope = Some(registry.scope);
+    artifacts::update(|artifacts| artifacts.add_created(&plan, scope));
+    for icon in icons.iter() {
+        write_icon(icon)?;
+    }
+    plan.apply(registry)
+        .map_err(|e| RegistrationError::FileError(e.error, e.target))?;
+    for change in plan.changes.iter() {
+        if let Change::DeleteFile(path) = change {
+            remove_generated_icon(Path::new(path))?;
         }
-    };
-    update_value(
-        &app_id_sub_key,
-        "DisplayName",
-        display_name.or(Some(app_id.to_string())),
-    )?;
-    update_value(&app_id_sub_key, "IconUri", icon_path)?;
+    }
+    artifacts::update(|artifacts| artifacts.remove_deleted(&plan, scope));
+    println_pipe!("{}", plan.to_string().trim_end());
     Ok(())
 }
 
-fn update_value(key: &RegKey, name: &str, value: Option<String>) -> Result<(), RegistrationError> {
-    if let Some(val) = value {
-        if key.get_raw_value(name).is_ok() {
-            println_pipe!("Exists");
-            key.delete_value(&name)
-                .map_err(|e| RegistrationError::FileError(e, format!("remove {}", name)))?;
-            println_pipe!("removed");
+/// Options passing the registry scope to the elevated process.
+pub fn scope_args(scope: Scope) -> Vec<String> {
+    vec!["--scope".to_string(), scope.to_string()]
+}
+
+/// Options of the register command which pass the registration to the elevated process.
+pub fn register_args(registration: &AppRegistration) -> Vec<String> {
+    [
+        ("-a", Some(registration.app_id.clone())),
+        ("-n", registration.display_name.clone()),
+        ("-i", registration.icon_path.clone()),
+        (
+            "--icon-background-color",
+            registration.icon_background_color.clone(),
+        ),
+        (
+            "--show-in-settings",
+            registration.show_in_settings.map(|show| show.to_string()),
+        ),
+        ("--custom-activator", registration.custom_activator.clone()),
+        ("--launch-command", registration.launch_command.clone()),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| value.map(|value| [name.to_string(), value]))
+    .flatten()
+    .collect()
+}
+
+/// Icons of user registrations are stored in the local app data of the user, icons of machine
+/// registrations in ProgramData which all users can read.
+fn generated_icon_path(app_id: &str, scope: Scope) -> Result<PathBuf, RegistrationError> {
+    let data_dir = match scope {
+        Scope::User => dirs_next::data_local_dir(),
+        Scope::Machine => env::var_os("ProgramData").map(PathBuf::from),
+    };
+    let data_dir = data_dir.ok_or(RegistrationError::ArgumentError(format!(
+        "Could not find data dir of {} scope",
+        scope
+    )))?;
+    Ok(icon::generated_icon_path(&data_dir, app_id))
+}
+
+/// PNG converted from an icon and the path it is stored at.
+struct ConvertedIcon {
+    path: PathBuf,
+    png: Vec<u8>,
+}
+
+/// Converts icon which is not a PNG. Returns the registration with the converted icon path
+/// and the PNG when it differs from the already converted one.
+fn convert_icon(
+    registration: &AppRegistration,
+    scope: Scope,
+) -> Result<(AppRegistration, Option<ConvertedIcon>), RegistrationError> {
+    let Some(icon_path) = &registration.icon_path else {
+        return Ok((registration.clone(), None));
+    };
+    let format =
+        icon::icon_format(Path::new(icon_path)).map_err(RegistrationError::ArgumentError)?;
+    if format == IconFormat::Png {
+        return Ok((registration.clone(), None));
+    }
+    let data =
+        std::fs::read(icon_path).map_err(|e| RegistrationError::FileError(e, icon_path.clone()))?;
+    let png = icon::convert_to_png(&data, format, icon::ICON_SIZE)
+        .map_err(RegistrationError::ArgumentError)?;
+    let path = generated_icon_path(&registration.app_id, scope)?;
+    let converted = AppRegistration {
+        icon_path: Some(path.display().to_string()),
+        ..registration.clone()
+    };
+    match std::fs::read(&path) {
+        Ok(existing) if existing == png => Ok((converted, None)),
+        _ => Ok((converted, Some(ConvertedIcon { path, png }))),
+    }
+}
+
+/// Path of the icon generated for the application id of `scope`, None when there is none or
+/// when `IconUri` of an application id of either scope, except the `removed` ones of `scope`,
+/// still points to it.
+fn unused_generated_icon(
+    app_id: &str,
+    scope: Scope,
+    removed: &[String],
+) -> Result<Option<PathBuf>, RegistrationError> {
+    let icon_path = generated_icon_path(app_id, scope)?;
+    if metadata(&icon_path).is_err() {
+        return Ok(None);
+    }
+    let path = icon_path.display().to_string();
+    for users_scope in [Scope::User, Scope::Machine] {
+        let registry = WinRegistry::for_scope(users_scope);
+        let users =
+            registration::icon_users(&registry, &path).map_err(RegistrationError::ArgumentError)?;
+        let used = users.iter().any(|user| {
+            users_scope != scope || !removed.iter().any(|x| x.eq_ignore_ascii_case(user))
+        });
+        if used {
+            return Ok(None);
+        }
+    }
+    Ok(Some(icon_path))
+}
+
+fn remove_generated_icon(icon_path: &Path) -> Result<(), RegistrationError> {
+    if let Err(e) = std::fs::remove_file(icon_path) {
+        if e.kind() != ErrorKind::NotFound {
+            let path = icon_path.display().to_string();
+            return Err(RegistrationError::FileError(e, path));
         }
-        key.set_value(&name, &val)
-            .map_err(|e| RegistrationError::FileError(e, name.to_string()))?;
-        println_pipe!("{} set to {}", name, val);
+    }
+    if let Some(dir) = icon_path.parent() {
+        std::fs::remove_dir(dir).ok();
     }
     Ok(())
 }
 
-pub fn un_register_app_id_fallback(path: &String) -> Result<(), String> {
+fn write_icon(icon: &ConvertedIcon) -> Result<(), RegistrationError> {
+    let path = &icon.path;
+    std::fs::create_dir_all(path.parent().unwrap_or(path))
+        .and_then(|_| std::fs::write(path, &icon.png))
+        .map_err(|e| RegistrationError::FileError(e, path.display().to_string()))
+}
+
+pub fn unregister_app_id(
+    app_id: String,
+    scope: Scope,
+    dry_run: bool,
+) -> Result<(), RegistrationError> {
+    let mut registry = WinRegistry::for_scope(scope);
+    let mut plan = registration::plan_unregister(&registry, &app_id)
+        .map_err(RegistrationError::ArgumentError)?;
+    if let Some(icon_path) = unused_generated_icon(&app_id, scope, std::slice::from_ref(&app_id))? {
+        plan.changes
+            .push(Change::DeleteFile(icon_path.display().to_string()));
+    }
+    apply_plan(&mut registry, plan, &[], dry_run)
+}
+
+pub fn register_app_id(
+    registration: &AppRegistration,
+    scope: Scope,
+    dry_run: bool,
+) -> Result<(), RegistrationError> {
+    let mut registry = WinRegistry::for_scope(scope);
+    let (registration, icon) = convert_icon(registration, scope)?;
+    let mut plan = registration::plan_register(&registry, &registration)
+        .map_err(RegistrationError::ArgumentError)?;
+    let icons: Vec<ConvertedIcon> = icon.into_iter().collect();
+    for icon in icons.iter() {
+        plan.changes
+            .push(Change::CreateFile(icon.path.display().to_string()));
+    }
+    apply_plan(&mut registry, plan, &icons, dry_run)
+}
+
+pub fn read_manifest(path: &Path) -> Result<Manifest, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    manifest::parse_manifest(&text, base_dir)
+}
+
+/// Registers manifest applications of `scope` and with `prune` unregisters applications created
+/// by this tool which are not listed. Applications which are registered already are unchanged.
+pub fn sync_manifest(
+    manifest: &Manifest,
+    scope: Scope,
+    prune: bool,
+    dry_run: bool,
+) -> Result<(), RegistrationError> {
+    let mut registry = WinRegistry::for_scope(scope);
+    let mut registrations = vec![];
+    let mut icons = vec![];
+    for registration in manifest.registrations(scope) {
+        let (registration, icon) = convert_icon(&registration, scope)?;
+        registrations.push(registration);
+        icons.extend(icon);
+    }
+    let remove = match prune {
+        true => registration::unlisted_app_ids(&registry, &registrations)
+            .map_err(RegistrationError::ArgumentError)?,
+        false => vec![],
+    };
+    let mut plan = registration::plan_sync(&registry, &registrations, &remove)
+        .map_err(RegistrationError::ArgumentError)?;
+    for icon in icons.iter() {
+        plan.changes
+            .push(Change::CreateFile(icon.path.display().to_string()));
+    }
+    for app_id in remove.iter() {
+        if let Some(icon_path) = unused_generated_icon(app_id, scope, &remove)? {
+            plan.changes
+                .push(Change::DeleteFile(icon_path.display().to_string()));
+        }
+    }
+    apply_plan(&mut registry, plan, &icons, dry_run)
+}
+
+pub fn un_register_app_id_fallback(path: &String, dry_run: bool) -> Result<(), String> {
     let link_name = get_link_name(&path);
     let destination = get_link_path(link_name)?;
     if metadata(&destination).is_ok() {
+        if dry_run {
+            print!(
+                "{}",
+                file_plan(Change::DeleteFile(destination.display().to_string()))
+            );
+            return Ok(());
+        }
         std::fs::remove_file(destination.clone()).map_err(|e| e.to_string())?;
+        artifacts::forget(Artifact::File {
+            path: destination.display().to_string(),
+        });
         println!("Removed: {}", destination.to_str().unwrap());
+    } else if dry_run {
+        print!("{}", Plan::new(""));
     }
     Ok(())
 }
 
-fn get_link_path(link_name: String) -> Result<PathBuf, String> {
+fn file_plan(change: Change) -> Plan {
+    let mut plan = Plan::new("");
+    plan.changes.push(change);
+    plan
+}
+
+/// Start menu programs directory of the current user.
+pub fn programs_dir() -> Result<PathBuf, String> {
     Ok(dirs_next::home_dir()
         .ok_or("Could not find home dir")?
-        .join(r"AppData\Roaming\Microsoft\Windows\Start Menu\Programs")
-        .join(format!("{link_name}.lnk")))
+        .join(r"AppData\Roaming\Microsoft\Windows\Start Menu\Programs"))
+}
+
+fn get_link_path(link_name: String) -> Result<PathBuf, String> {
+    Ok(programs_dir()?.join(format!("{link_name}.lnk")))
 }
 
-pub fn register_app_id_fallback(app_id: &String) -> Result<(), String> {
+/// Registered application ids of both scopes and shortcuts in the Start menu programs directory.
+pub fn list() -> Result<(Vec<RegisteredApp>, Vec<Shortcut>), String> {
+    let mut apps = vec![];
+    for scope in [Scope::User, Scope::Machine] {
+        let registry = WinRegistry::for_scope(scope);
+        apps.extend(inventory::list_registrations(
+            &registry,
+            registration::APP_USER_MODEL_ID,
+            scope,
+        )?);
+    }
+    let shortcuts = inventory::list_shortcuts(&programs_dir()?)?;
+    Ok((apps, shortcuts))
+}
+
+/// Creates a Start menu shortcut to the executable `registration.app_id` stamped with it as
+/// `System.AppUserModel.ID`, with the icon and the custom activator of the registration.
+/// An existing shortcut is rewritten when it was created by this tool or has no application id.
+pub fn register_app_id_fallback(
+    registration: &AppRegistration,
+    arguments: Option<String>,
+    dry_run: bool,
+) -> Result<(), String> {
+    let app_id = &registration.app_id;
     let path = app_id.to_string();
     if !metadata(&app_id).is_ok() {
         return Ok(());
     };
     let link_name = get_link_name(&path);
-    let destination = get_link_path(link_name.clone())?;
-    if metadata(&destination).is_ok() {
-        return Ok(());
-    }
     if !sanitize_filename::is_sanitized(&link_name) {
         return Err(format!(
             "[{}] contains invalid file name characters",
             &link_name
         ));
     }
-    let link = mslnk::ShellLink::new(&path).map_err(|e| e.to_string())?;
-    link.create_lnk(destination.clone())
-        .map_err(|e| e.to_string())?;
-    println!("Registered in {}", destination.to_str().unwrap());
+    let destination = get_link_path(link_name.clone())?;
+    let destination_path = destination.display().to_string();
+    let target = std::fs::canonicalize(&path).map_err(|e| e.to_string())?;
+    let target = target.display().to_string();
+    let link = ShellLink {
+        name: Some(registration::SHORTCUT_DESCRIPTION.to_string()),
+        arguments,
+        icon_location: registration
+            .icon_path
+            .clone()
+            .filter(|icon| is_link_icon(icon)),
+        app_user_model_id: Some(path.clone()),
+        toast_activator_clsid: registration.custom_activator.clone(),
+        ..ShellLink::new(target.strip_prefix(r"\\?\").unwrap_or(&target))
+    };
+    let (link, plan) = match std::fs::read(&destination) {
+        Ok(data) => {
+            let existing = lnk::read_shell_link(&data)
+                .map_err(|e| format!("Could not read {}: {}", destination_path, e))?;
+            let Some(link) = lnk::update_shell_link(&existing, &link) else {
+                if dry_run {
+                    print!("{}", Plan::new(""));
+                }
+                return Ok(());
+            };
+            let mut plan = file_plan(Change::DeleteFile(destination_path.clone()));
+            plan.changes
+                .push(Change::CreateFile(destination_path.clone()));
+            (link, plan)
+        }
+        Err(_) => (
+            link,
+            file_plan(Change::CreateFile(destination_path.clone())),
+        ),
+    };
+    if dry_run {
+        print!("{}", plan);
+        return Ok(());
+    }
+    let data = lnk::write_shell_link(&link)?;
+    if link.name.as_deref() == Some(registration::SHORTCUT_DESCRIPTION) {
+        artifacts::record(Artifact::File {
+            path: destination_path,
+        });
+    }
+    std::fs::write(&destination, data).map_err(|e| e.to_string())?;
+    println!("{}", plan.to_string().trim_end());
     Ok(())
 }
 
+/// Shortcut icons are read from icon files and executables, other icons are left to the target.
+fn is_link_icon(icon: &str) -> bool {
+    Path::new(icon)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ["ico", "exe", "dll"].contains(&ext.to_lowercase().as_str()))
+}
+
 fn get_link_name(path: &String) -> String {
     PathBuf::from(path.clone())
         .file_stem()
@@ -0,0 +1,186 @@
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+pub const TIMESTAMP_HEADER: &str = "X-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+pub const DEFAULT_MAX_SKEW_SECS: u64 = 300;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum AuthMode {
+    /// Only Api-Key header is accepted.
+    ApiKey,
+    /// Only signed requests are accepted.
+    Signature,
+    /// Both Api-Key header and signed requests are accepted.
+    Any,
+}
+
+/// String covered by the signature:
+/// `METHOD\nPATH_AND_QUERY\nTIMESTAMP\nhex(sha256(BODY))`.
+pub fn canonical_request(
+    method: &str,
+    path_and_query: &str,
+    timestamp: &str,
+    body: &[u8],
+) -> String {
+    format!(
+        "{}\n{}\n{}\n{}",
+        method.to_ascii_uppercase(),
+        path_and_query,
+        timestamp,
+        hex::encode(Sha256::digest(body))
+    )
+}
+
+/// Hex encoded HMAC-SHA256 of the canonical request keyed with the shared secret.
+#[cfg(test)]
+pub fn sign(
+    secret: &[u8],
+    method: &str,
+    path_and_query: &str,
+    timestamp: &str,
+    body: &[u8],
+) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(canonical_request(method, path_and_query, timestamp, body).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Verifies signed requests and remembers accepted signatures while their timestamp
+/// is inside the allowed clock skew, so each signed request is accepted only once.
+pub struct SignatureVerifier {
+    max_skew_secs: u64,
+    seen: Mutex<HashMap<String, i64>>,
+}
+
+impl SignatureVerifier {
+    pub fn new(max_skew_secs: u64) -> SignatureVerifier {
+        SignatureVerifier {
+            max_skew_secs,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify(
+        &self,
+        secret: &[u8],
+        method: &str,
+        path_and_query: &str,
+        timestamp: &str,
+        signature: &str,
+        body: &[u8],
+        now: i64,
+    ) -> Result<(), String> {
+        let request_time = timestamp
+            .trim()
+            .parse::<i64>()
+            .map_err(|_| "invalid timestamp".to_string())?;
+        if request_time.abs_diff(now) > self.max_skew_secs {
+            return Err("timestamp outside of allowed window".to_string());
+        }
+        let signature =
+            hex::decode(signature.trim()).map_err(|_| "invalid signature".to_string())?;
+        let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any size");
+        mac.update(canonical_request(method, path_and_query, timestamp.trim(), body).as_bytes());
+        mac.verify_slice(&signature)
+            .map_err(|_| "signature mismatch".to_string())?;
+        let mut seen = self
+            .seen
+            .lock()
+            .map_err(|_| "replay cache unavailable".to_string())?;
+        let oldest_allowed = now - self.max_skew_secs as i64;
+        seen.retain(|_, time| *time >= oldest_allowed);
+        let key = hex::encode(&signature);
+        if seen.contains_key(&key) {
+            return Err("signature already used".to_string());
+        }
+        seen.insert(key, request_time);
+        Ok(())
+    }
+}
+
+#[test]
+fn signature_verify_test() {
+    let verifier = SignatureVerifier::new(DEFAULT_MAX_SKEW_SECS);
+    let body = br#"{"toast_xml":"<toast/>"}"#;
+    let signature = sign(b"secret", "POST", "/notify", "1000", body);
+    assert_eq!(
+        verifier.verify(b"other", "POST", "/notify", "1000", &signature, body, 1000),
+        Err("signature mismatch".to_string())
+    );
+    assert_eq!(
+        verifier.verify(b"secret", "POST", "/notify", "1000", &signature, b"{}", 1000),
+        Err("signature mismatch".to_string())
+    );
+    assert_eq!(
+        verifier.verify(b"secret", "DELETE", "/all", "1000", &signature, body, 1000),
+        Err("signature mismatch".to_string())
+    );
+    assert_eq!(
+        verifier.verify(b"secret", "POST", "/notify", "1000", &signature, body, 1010),
+        Ok(())
+    );
+    assert_eq!(
+        verifier.verify(b"secret", "POST", "/notify", "1000", &signature, body, 1020),
+        Err("signature already used".to_string())
+    );
+}
+
+#[test]
+fn signature_clock_skew_test() {
+    let verifier = SignatureVerifier::new(60);
+    let signature = sign(b"secret", "GET", "/status-stream?from=2", "1000", b"");
+    assert_eq!(
+        verifier.verify(
+            b"secret",
+            "GET",
+            "/status-stream?from=2",
+            "1000",
+            &signature,
+            b"",
+            1061
+        ),
+        Err("timestamp outside of allowed window".to_string())
+    );
+    assert_eq!(
+        verifier.verify(
+            b"secret",
+            "GET",
+            "/status-stream?from=2",
+            "1000",
+            &signature,
+            b"",
+            939
+        ),
+        Err("timestamp outside of allowed window".to_string())
+    );
+    assert_eq!(
+        verifier.verify(
+            b"secret",
+            "GET",
+            "/status-stream?from=2",
+            "abc",
+            &signature,
+            b"",
+            1000
+        ),
+        Err("invalid timestamp".to_string())
+    );
+    assert_eq!(
+        verifier.verify(
+            b"secret",
+            "GET",
+            "/status-stream?from=2",
+            "1000",
+            &signature,
+            b"",
+            1060
+        ),
+        Ok(())
+    );
+}
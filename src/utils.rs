@@ -1,5 +1,15 @@
 use rand::distributions::Alphanumeric;
 use rand::Rng;
+use std::path::Path;
+use windows::core::HSTRING;
+use windows::Win32::Foundation::{LocalFree, HLOCAL};
+use windows::Win32::Security::Authorization::{
+    ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+};
+use windows::Win32::Security::{
+    SetFileSecurityW, DACL_SECURITY_INFORMATION, PROTECTED_DACL_SECURITY_INFORMATION,
+    PSECURITY_DESCRIPTOR,
+};
 
 pub fn get_random_string(len: usize) -> String {
     rand::thread_rng()
@@ -9,38 +19,60 @@ pub fn get_random_string(len: usize) -> String {
         .collect()
 }
 
-#[test]
-fn create_sample_notification_test() {
-    let res = create_sample_notification("hello", "world", Some("yes,no".to_string()));
-    println!("{}", res)
+/// Quotes command line argument so that it is parsed back as is by CommandLineToArgvW.
+pub fn quote_arg(arg: &str) -> String {
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            c => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
 }
 
-pub fn create_sample_notification(title: &str, message: &str, buttons: Option<String>) -> String {
-    let mut actions = String::new();
-    if let Some(btn) = buttons {
-        let actions_list: Vec<String> = btn
-            .split(',')
-            .map(|b| format!("<action arguments=\"{}\" content=\"{}\"/>", b, b).to_string())
-            .collect();
-        if actions_list.len() > 0 {
-            actions.push_str("<actions>");
-            actions.push_str(actions_list.join("\n").as_str());
-            actions.push_str("</actions>");
-        }
+/// Writes file which only its owner can read, inherited permissions are removed.
+pub fn write_user_only_file(path: &Path, content: &str) -> Result<(), String> {
+    std::fs::write(path, "").map_err(|e| e.to_string())?;
+    let mut descriptor = PSECURITY_DESCRIPTOR::default();
+    unsafe {
+        ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            &HSTRING::from("D:P(A;;FA;;;OW)"),
+            SDDL_REVISION_1,
+            &mut descriptor,
+            None,
+        )
+        .map_err(|e| e.message().to_string())?;
+        let applied = SetFileSecurityW(
+            &HSTRING::from(path.as_os_str()),
+            (DACL_SECURITY_INFORMATION | PROTECTED_DACL_SECURITY_INFORMATION).0,
+            descriptor,
+        );
+        _ = LocalFree(HLOCAL(descriptor.0));
+        applied.ok().map_err(|e| e.message().to_string())?;
     }
-    let string = format!(
-        "
-<toast>
-  <visual>
-    <binding template=\"ToastGeneric\">
-      <text>{}</text>
-      <text>{}</text>
-    </binding>
-  </visual>
-    {}
-</toast>",
-        title, message, actions
-    )
-    .to_string();
-    string
+    std::fs::write(path, content).map_err(|e| e.to_string())
+}
+
+#[test]
+fn quote_arg_test() {
+    assert_eq!(quote_arg("app"), r#""app""#);
+    assert_eq!(quote_arg(r"C:\App\"), r#""C:\App\\""#);
+    assert_eq!(
+        quote_arg(r#""C:\App\app.exe" -ToastActivated"#),
+        r#""\"C:\App\app.exe\" -ToastActivated""#
+    );
+    assert_eq!(quote_arg(r#"a\"b"#), r#""a\\\"b""#);
 }
@@ -0,0 +1,158 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_MAX_XML_SIZE: u64 = 64 * 1024;
+
+const NOT_ALLOWED: &str = "toast_xml_path is not allowed";
+const NOT_READABLE: &str = "toast_xml_path could not be read";
+const TOO_LARGE: &str = "toast_xml_path file is too large";
+
+/// Decides which files may be read for `toast_xml_path` requests.
+///
+/// Paths are canonicalized before the check, so `..` segments and symlinks pointing
+/// outside of the allowed roots are rejected. Errors never contain the requested path.
+#[derive(Debug, Clone)]
+pub struct XmlPathPolicy {
+    allowed_roots: Vec<PathBuf>,
+    base_dir: Option<PathBuf>,
+    max_size: u64,
+}
+
+impl XmlPathPolicy {
+    /// Policy that rejects every path.
+    pub fn deny_all() -> XmlPathPolicy {
+        XmlPathPolicy {
+            allowed_roots: vec![],
+            base_dir: None,
+            max_size: DEFAULT_MAX_XML_SIZE,
+        }
+    }
+
+    /// Creates policy from configured roots. The base dir is used to resolve relative
+    /// paths and is allowed as well when no roots are specified.
+    pub fn new(
+        allowed_roots: Vec<String>,
+        base_dir: Option<String>,
+        max_size: u64,
+    ) -> Result<XmlPathPolicy, String> {
+        let canonical = |dir: &String| {
+            fs::canonicalize(dir).map_err(|e| format!("Invalid xml directory {}: {}", dir, e))
+        };
+        let base_dir = base_dir.as_ref().map(canonical).transpose()?;
+        let mut roots = allowed_roots
+            .iter()
+            .map(canonical)
+            .collect::<Result<Vec<PathBuf>, String>>()?;
+        if roots.is_empty() {
+            roots.extend(base_dir.clone());
+        }
+        Ok(XmlPathPolicy {
+            allowed_roots: roots,
+            base_dir,
+            max_size,
+        })
+    }
+
+    pub fn read(&self, path: &str) -> Result<String, String> {
+        let path = self.resolve(path).map_err(|e| {
+            eprintln!("Rejected toast_xml_path {}: {}", path, e);
+            e
+        })?;
+        let file = fs::File::open(&path).map_err(|_| NOT_READABLE.to_string())?;
+        let mut content = String::new();
+        file.take(self.max_size + 1)
+            .read_to_string(&mut content)
+            .map_err(|_| NOT_READABLE.to_string())?;
+        if content.len() as u64 > self.max_size {
+            return Err(TOO_LARGE.to_string());
+        }
+        Ok(content)
+    }
+
+    fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        if self.allowed_roots.is_empty() {
+            return Err(NOT_ALLOWED.to_string());
+        }
+        let requested = Path::new(path);
+        let requested = if requested.is_absolute() {
+            requested.to_path_buf()
+        } else {
+            match &self.base_dir {
+                Some(base_dir) => base_dir.join(requested),
+                None => return Err(NOT_ALLOWED.to_string()),
+            }
+        };
+        let canonical = fs::canonicalize(requested).map_err(|_| NOT_ALLOWED.to_string())?;
+        if !self
+            .allowed_roots
+            .iter()
+            .any(|root| canonical.starts_with(root))
+        {
+            return Err(NOT_ALLOWED.to_string());
+        }
+        let metadata = fs::metadata(&canonical).map_err(|_| NOT_READABLE.to_string())?;
+        if !metadata.is_file() {
+            return Err(NOT_ALLOWED.to_string());
+        }
+        if metadata.len() > self.max_size {
+            return Err(TOO_LARGE.to_string());
+        }
+        Ok(canonical)
+    }
+}
+
+#[cfg(test)]
+fn create_test_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("xml-policy-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(dir.join("allowed")).unwrap();
+    fs::write(dir.join("allowed").join("toast.xml"), "<toast/>").unwrap();
+    fs::write(dir.join("secret.xml"), "<secret/>").unwrap();
+    dir
+}
+
+#[test]
+fn xml_policy_allows_files_in_root_test() {
+    let dir = create_test_dir();
+    let root = dir.join("allowed").display().to_string();
+    let policy = XmlPathPolicy::new(vec![root.clone()], None, DEFAULT_MAX_XML_SIZE).unwrap();
+    let path = dir.join("allowed").join("toast.xml").display().to_string();
+    assert_eq!(policy.read(&path).unwrap(), "<toast/>");
+    assert_eq!(
+        policy.read("toast.xml").unwrap_err(),
+        NOT_ALLOWED,
+        "relative paths require base dir"
+    );
+    let policy = XmlPathPolicy::new(vec![], Some(root), DEFAULT_MAX_XML_SIZE).unwrap();
+    assert_eq!(policy.read("toast.xml").unwrap(), "<toast/>");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn xml_policy_rejects_traversal_test() {
+    let dir = create_test_dir();
+    let root = dir.join("allowed").display().to_string();
+    let policy = XmlPathPolicy::new(vec![], Some(root), DEFAULT_MAX_XML_SIZE).unwrap();
+    let escaped = dir.join("allowed").join("..").join("secret.xml");
+    assert_eq!(
+        policy.read(&escaped.display().to_string()).unwrap_err(),
+        NOT_ALLOWED
+    );
+    assert_eq!(policy.read("../secret.xml").unwrap_err(), NOT_ALLOWED);
+    assert_eq!(policy.read("missing.xml").unwrap_err(), NOT_ALLOWED);
+    assert_eq!(policy.read(".").unwrap_err(), NOT_ALLOWED);
+    assert_eq!(
+        XmlPathPolicy::deny_all().read("toast.xml").unwrap_err(),
+        NOT_ALLOWED
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn xml_policy_limits_size_test() {
+    let dir = create_test_dir();
+    let root = dir.join("allowed").display().to_string();
+    let policy = XmlPathPolicy::new(vec![], Some(root), 4).unwrap();
+    assert_eq!(policy.read("toast.xml").unwrap_err(), TOO_LARGE);
+    fs::remove_dir_all(dir).unwrap();
+}
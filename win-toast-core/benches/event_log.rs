@@ -0,0 +1,103 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tokio::task::JoinHandle;
+use win_toast_core::event_log::{event_log, LogItem, Sender};
+
+const SUBSCRIBERS: [usize; 3] = [1, 100, 1000];
+const EVENTS: usize = 1000;
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+/// Spawns subscribers which read `count` events starting at `from`.
+async fn spawn_readers(
+    sender: &Sender<usize>,
+    subscribers: usize,
+    from: usize,
+    count: usize,
+) -> Vec<JoinHandle<()>> {
+    let mut readers = Vec::with_capacity(subscribers);
+    for _ in 0..subscribers {
+        let mut subscriber = sender.subscribe_from(from).await;
+        readers.push(tokio::spawn(async move {
+            for _ in 0..count {
+                match subscriber.recv().await {
+                    Some(LogItem::Event(..)) => {}
+                    item => panic!("unexpected {:?}", item),
+                }
+            }
+        }));
+    }
+    readers
+}
+
+/// Time until every subscriber received all published events.
+fn throughput(c: &mut Criterion) {
+    let runtime = runtime();
+    let mut group = c.benchmark_group("event_log_throughput");
+    group.sample_size(10);
+    for subscribers in SUBSCRIBERS {
+        group.throughput(Throughput::Elements((EVENTS * subscribers) as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(subscribers),
+            &subscribers,
+            |b, &subscribers| {
+                b.to_async(&runtime).iter_custom(|iters| async move {
+                    let mut total = Duration::ZERO;
+                    for _ in 0..iters {
+                        let sender = event_log::<usize>(EVENTS);
+                        let readers = spawn_readers(&sender, subscribers, 0, EVENTS).await;
+                        let start = Instant::now();
+                        for i in 0..EVENTS {
+                            sender.send(i).await.unwrap();
+                        }
+                        for reader in readers {
+                            reader.await.unwrap();
+                        }
+                        total += start.elapsed();
+                    }
+                    total
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Time from publishing one event until every subscriber received it.
+fn latency(c: &mut Criterion) {
+    let runtime = runtime();
+    let mut group = c.benchmark_group("event_log_latency");
+    group.sample_size(10);
+    for subscribers in SUBSCRIBERS {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(subscribers),
+            &subscribers,
+            |b, &subscribers| {
+                b.to_async(&runtime).iter_custom(|iters| async move {
+                    let sender = event_log::<usize>(EVENTS);
+                    let mut total = Duration::ZERO;
+                    for i in 0..iters as usize {
+                        let readers = spawn_readers(&sender, subscribers, i, 1).await;
+                        let start = Instant::now();
+                        sender.send(i).await.unwrap();
+                        for reader in readers {
+                            reader.await.unwrap();
+                        }
+                        total += start.elapsed();
+                    }
+                    total
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, throughput, latency);
+criterion_main!(benches);
@@ -0,0 +1,341 @@
+//! Artifacts created by this tool, kept in a local file so that `un-register --all` removes them.
+//!
+//! Created artifacts are recorded before they are created and forgotten after they are removed,
+//! so an interrupted registration or uninstall leaves nothing behind which is not recorded.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::registration::{Change, Plan, Scope};
+use crate::registry::Registry;
+
+/// Registry key, registry value or file created by this tool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Artifact {
+    RegistryKey {
+        scope: Scope,
+        key: String,
+    },
+    /// Value set in a key which existed before.
+    RegistryValue {
+        scope: Scope,
+        key: String,
+        name: String,
+    },
+    File {
+        path: String,
+    },
+    /// Directory removed with its content.
+    Directory {
+        path: String,
+    },
+}
+
+/// Path of the artifacts file in the local data directory.
+pub fn artifacts_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("win-toast-notifier").join("artifacts.json")
+}
+
+/// Registry key is `key` or one of its subkeys.
+fn is_below(child: &str, key: &str) -> bool {
+    let (child, key) = (child.to_lowercase(), key.to_lowercase());
+    child == key || child.starts_with(&format!("{}\\", key))
+}
+
+impl Artifact {
+    fn is_same(&self, other: &Artifact) -> bool {
+        match (self, other) {
+            (
+                Artifact::RegistryKey { scope, key },
+                Artifact::RegistryKey {
+                    scope: other_scope,
+                    key: other_key,
+                },
+            ) => scope == other_scope && key.eq_ignore_ascii_case(other_key),
+            (
+                Artifact::RegistryValue { scope, key, name },
+                Artifact::RegistryValue {
+                    scope: other_scope,
+                    key: other_key,
+                    name: other_name,
+                },
+            ) => {
+                scope == other_scope
+                    && key.eq_ignore_ascii_case(other_key)
+                    && name.eq_ignore_ascii_case(other_name)
+            }
+            (Artifact::File { path }, Artifact::File { path: other })
+            | (Artifact::Directory { path }, Artifact::Directory { path: other }) => {
+                path.eq_ignore_ascii_case(other)
+            }
+            _ => false,
+        }
+    }
+
+    /// Artifact is removed together with the registry key.
+    fn is_in_key(&self, deleted_scope: Scope, deleted_key: &str) -> bool {
+        match self {
+            Artifact::RegistryKey { scope, key } | Artifact::RegistryValue { scope, key, .. } => {
+                *scope == deleted_scope && is_below(key, deleted_key)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Artifacts {
+    pub artifacts: Vec<Artifact>,
+}
+
+impl Artifacts {
+    /// Parses the artifacts file, an empty file has no artifacts.
+    pub fn parse(text: &str) -> Result<Artifacts, String> {
+        if text.trim().is_empty() {
+            return Ok(Artifacts::default());
+        }
+        serde_json::from_str(text).map_err(|e| format!("Invalid artifacts file: {}", e))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.artifacts.is_empty()
+    }
+
+    pub fn add(&mut self, artifact: Artifact) {
+        if !self.artifacts.iter().any(|known| known.is_same(&artifact)) {
+            self.artifacts.push(artifact);
+        }
+    }
+
+    pub fn remove(&mut self, artifact: &Artifact) {
+        self.artifacts.retain(|known| !known.is_same(artifact));
+    }
+
+    /// Adds keys, values of existing keys and files created by the plan. Registry changes
+    /// are in `scope`, plans without registry changes may have none.
+    pub fn add_created(&mut self, plan: &Plan, scope: Option<Scope>) {
+        let mut created_keys: Vec<&str> = vec![];
+        for change in plan.changes.iter() {
+            match (change, scope) {
+                (Change::CreateKey(key), Some(scope)) => {
+                    created_keys.push(key);
+                    self.add(Artifact::RegistryKey {
+                        scope,
+                        key: key.clone(),
+                    });
+                }
+                (
+                    Change::SetValue {
+                        key,
+                        name,
+                        old: None,
+                        ..
+                    },
+                    Some(scope),
+                ) if !created_keys.iter().any(|created| is_below(key, created)) => {
+                    self.add(Artifact::RegistryValue {
+                        scope,
+                        key: key.clone(),
+                        name: name.clone(),
+                    });
+                }
+                (Change::CreateFile(path), _) => self.add(Artifact::File { path: path.clone() }),
+                _ => {}
+            }
+        }
+    }
+
+    /// Removes artifacts deleted by the plan, including keys and values below deleted keys.
+    pub fn remove_deleted(&mut self, plan: &Plan, scope: Option<Scope>) {
+        for change in plan.changes.iter() {
+            match (change, scope) {
+                (Change::DeleteKey { key, .. }, Some(scope)) => {
+                    self.artifacts
+                        .retain(|artifact| !artifact.is_in_key(scope, key));
+                }
+                (Change::DeleteValue { key, name, .. }, Some(scope)) => {
+                    self.remove(&Artifact::RegistryValue {
+                        scope,
+                        key: key.clone(),
+                        name: name.clone(),
+                    });
+                }
+                (Change::DeleteFile(path), _) => {
+                    self.remove(&Artifact::File { path: path.clone() })
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Changes which remove registry artifacts of `scope` which still exist: values first, then keys
+/// with their subkeys.
+pub fn plan_remove(
+    registry: &dyn Registry,
+    artifacts: &Artifacts,
+    scope: Scope,
+) -> Result<Plan, String> {
+    let mut keys: Vec<&str> = vec![];
+    for artifact in artifacts.artifacts.iter() {
+        if let Artifact::RegistryKey {
+            scope: key_scope,
+            key,
+        } = artifact
+        {
+            if *key_scope == scope && registry.key_exists(key).map_err(|e| e.to_string())? {
+                keys.push(key);
+            }
+        }
+    }
+    let is_deleted = |key: &str| keys.iter().any(|deleted| is_below(key, deleted));
+    let mut plan = Plan::new(&registry.root_name());
+    for artifact in artifacts.artifacts.iter() {
+        if let Artifact::RegistryValue {
+            scope: value_scope,
+            key,
+            name,
+        } = artifact
+        {
+            if *value_scope != scope || is_deleted(key) {
+                continue;
+            }
+            if let Some(old) = registry.get_value(key, name).map_err(|e| e.to_string())? {
+                plan.changes.push(Change::DeleteValue {
+                    key: key.clone(),
+                    name: name.clone(),
+                    old,
+                });
+            }
+        }
+    }
+    let top_keys = keys.iter().filter(|key| {
+        !keys
+            .iter()
+            .any(|parent| !parent.eq_ignore_ascii_case(key) && is_below(key, parent))
+    });
+    for key in top_keys {
+        let values = registry.values(key).map_err(|e| e.to_string())?;
+        plan.changes.push(Change::DeleteKey {
+            key: key.to_string(),
+            values,
+        });
+    }
+    Ok(plan)
+}
+
+#[cfg(test)]
+use crate::registration::{plan_register, plan_unregister, AppRegistration};
+#[cfg(test)]
+use crate::registry::{MemoryRegistry, RegValue};
+
+#[test]
+fn record_plan_test() {
+    let mut registry = MemoryRegistry::new("HKEY_CURRENT_USER\\Software\\Classes");
+    registry.create_key(r"AppUserModelId\Existing").unwrap();
+    let mut artifacts = Artifacts::default();
+    for app_id in ["App", "Existing"] {
+        let registration = AppRegistration {
+            display_name: Some("Name".into()),
+            ..AppRegistration::new(app_id)
+        };
+        let mut plan = plan_register(&registry, &registration).unwrap();
+        plan.changes.push(Change::CreateFile(r"C:\icon.png".into()));
+        artifacts.add_created(&plan, Some(Scope::User));
+        plan.apply(&mut registry).unwrap();
+    }
+    assert_eq!(
+        artifacts.artifacts,
+        vec![
+            Artifact::RegistryKey {
+                scope: Scope::User,
+                key: r"AppUserModelId\App".into()
+            },
+            Artifact::File {
+                path: r"C:\icon.png".into()
+            },
+            Artifact::RegistryValue {
+                scope: Scope::User,
+                key: r"AppUserModelId\Existing".into(),
+                name: "DisplayName".into()
+            },
+        ]
+    );
+    let plan = plan_unregister(&registry, "app").unwrap();
+    artifacts.remove_deleted(&plan, Some(Scope::Machine));
+    assert_eq!(artifacts.artifacts.len(), 3);
+    artifacts.remove_deleted(&plan, Some(Scope::User));
+    assert_eq!(artifacts.artifacts.len(), 2);
+    let parsed = Artifacts::parse(&artifacts.to_json()).unwrap();
+    assert_eq!(parsed, artifacts);
+    assert!(artifacts.to_json().contains(r#""type": "registryValue""#));
+    assert_eq!(Artifacts::parse("").unwrap(), Artifacts::default());
+}
+
+#[test]
+fn plan_remove_test() {
+    let mut registry = MemoryRegistry::new("HKEY_CURRENT_USER\\Software\\Classes");
+    registry.create_key(r"AppUserModelId\Existing").unwrap();
+    let registration = AppRegistration {
+        custom_activator: Some("c3d7a5e8-0f3b-4d6c-9a8e-2b1f0e7d6c5a".into()),
+        launch_command: Some("app.exe".into()),
+        ..AppRegistration::new("App")
+    };
+    let mut artifacts = Artifacts::default();
+    let existing = AppRegistration {
+        display_name: Some("Name".into()),
+        ..AppRegistration::new("Existing")
+    };
+    for registration in [registration, existing] {
+        let plan = plan_register(&registry, &registration).unwrap();
+        artifacts.add_created(&plan, Some(Scope::User));
+        plan.apply(&mut registry).unwrap();
+    }
+    artifacts.add(Artifact::RegistryKey {
+        scope: Scope::User,
+        key: r"AppUserModelId\Removed".into(),
+    });
+    assert!(plan_remove(&registry, &artifacts, Scope::Machine)
+        .unwrap()
+        .is_empty());
+    let plan = plan_remove(&registry, &artifacts, Scope::User).unwrap();
+    let summary: Vec<String> = plan
+        .changes
+        .iter()
+        .map(|change| match change {
+            Change::DeleteKey { key, .. } => format!("key {}", key),
+            Change::DeleteValue { key, name, .. } => format!("value {} {}", key, name),
+            change => format!("{:?}", change),
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            r"value AppUserModelId\Existing DisplayName",
+            r"key AppUserModelId\App",
+            r"key CLSID\{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}",
+        ]
+    );
+    plan.apply(&mut registry).unwrap();
+    artifacts.remove_deleted(&plan, Some(Scope::User));
+    assert_eq!(
+        artifacts.artifacts,
+        vec![Artifact::RegistryKey {
+            scope: Scope::User,
+            key: r"AppUserModelId\Removed".into()
+        }]
+    );
+    assert!(registry.key_exists(r"AppUserModelId\Existing").unwrap());
+    assert_eq!(
+        registry
+            .get_value(r"AppUserModelId\Existing", "DisplayName")
+            .unwrap(),
+        None::<RegValue>
+    );
+}
@@ -0,0 +1,327 @@
+//! Checks of the `doctor` command which explain why toasts of an application id are not shown.
+//!
+//! Checks which need Windows APIs are made by the binary, this module judges their results.
+
+use std::fmt::{Display, Formatter};
+
+use serde::Serialize;
+
+use crate::inventory::{RegisteredApp, Shortcut};
+use crate::registration::validate_app_id;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    /// Toasts may still be shown.
+    Warn,
+    /// Toasts are not shown.
+    Fail,
+    /// Check does not apply or depends on a failed check.
+    Skip,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl Check {
+    pub fn new(name: &str, status: CheckStatus, detail: impl Into<String>) -> Check {
+        Check {
+            name: name.to_string(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    pub application_id: String,
+    /// No check failed.
+    pub passed: bool,
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    pub fn new(application_id: &str) -> Report {
+        Report {
+            application_id: application_id.to_string(),
+            passed: true,
+            checks: vec![],
+        }
+    }
+
+    pub fn push(&mut self, check: Check) {
+        self.passed &= check.status != CheckStatus::Fail;
+        self.checks.push(check);
+    }
+
+    pub fn failed(&self, name: &str) -> bool {
+        self.checks
+            .iter()
+            .any(|check| check.name == name && check.status == CheckStatus::Fail)
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Doctor report for {}", self.application_id)?;
+        for check in self.checks.iter() {
+            let status = match check.status {
+                CheckStatus::Pass => "pass",
+                CheckStatus::Warn => "warn",
+                CheckStatus::Fail => "FAIL",
+                CheckStatus::Skip => "skip",
+            };
+            writeln!(f, "  [{}] {}: {}", status, check.name, check.detail)?;
+        }
+        let failed = self
+            .checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Fail)
+            .count();
+        match failed {
+            0 => writeln!(f, "Result: pass"),
+            failed => writeln!(
+                f,
+                "Result: fail, {} of {} checks failed",
+                failed,
+                self.checks.len()
+            ),
+        }
+    }
+}
+
+pub const APP_ID_CHECK: &str = "app id";
+pub const REGISTRY_CHECK: &str = "registry entry";
+pub const ICON_CHECK: &str = "icon";
+pub const SHORTCUT_CHECK: &str = "shortcut";
+pub const ATTRIBUTION_CHECK: &str = "attribution";
+pub const NOTIFIER_CHECK: &str = "notifier";
+pub const SETTING_CHECK: &str = "setting";
+pub const PROBE_CHECK: &str = "probe toast";
+
+/// Syntax of the application id. `is_path` ids are executables attributed by their shortcut.
+pub fn check_app_id(app_id: &str, is_path: bool) -> Check {
+    if is_path {
+        return Check::new(
+            APP_ID_CHECK,
+            CheckStatus::Skip,
+            "application id is an executable path, toasts are attributed through its shortcut",
+        );
+    }
+    if let Err(error) = validate_app_id(app_id) {
+        return Check::new(APP_ID_CHECK, CheckStatus::Fail, error);
+    }
+    if app_id.contains(' ') {
+        return Check::new(
+            APP_ID_CHECK,
+            CheckStatus::Warn,
+            "application id should not contain spaces",
+        );
+    }
+    let segments: Vec<&str> = app_id.split('.').collect();
+    if segments.len() > 4 || segments.iter().any(|segment| segment.is_empty()) {
+        return Check::new(
+            APP_ID_CHECK,
+            CheckStatus::Warn,
+            "application id should have up to 4 non empty segments, \
+             CompanyName.ProductName.SubProduct.VersionInformation",
+        );
+    }
+    Check::new(APP_ID_CHECK, CheckStatus::Pass, "valid")
+}
+
+fn is_app_shortcut(shortcut: &Shortcut, app_id: &str) -> bool {
+    let matches = |value: &Option<String>| {
+        value
+            .as_ref()
+            .is_some_and(|value| value.eq_ignore_ascii_case(app_id))
+    };
+    matches(&shortcut.app_user_model_id) || matches(&shortcut.target)
+}
+
+/// Registry entry and its icon, the Start menu shortcut and whether Windows can attribute
+/// toasts to the application id by either of them.
+pub fn check_registration(
+    apps: &[RegisteredApp],
+    shortcuts: &[Shortcut],
+    app_id: &str,
+) -> Vec<Check> {
+    let mut checks = vec![];
+    let registered: Vec<&RegisteredApp> = apps
+        .iter()
+        .filter(|app| app.application_id.eq_ignore_ascii_case(app_id))
+        .collect();
+    match registered.first() {
+        Some(app) => {
+            let scopes: Vec<String> = registered.iter().map(|app| app.scope.to_string()).collect();
+            checks.push(Check::new(
+                REGISTRY_CHECK,
+                CheckStatus::Pass,
+                format!(
+                    "registered in {} scope as \"{}\"",
+                    scopes.join(" and "),
+                    app.display_name.as_deref().unwrap_or("")
+                ),
+            ));
+            let icon = match &app.icon_uri {
+                Some(icon) if app.icon_exists => Check::new(ICON_CHECK, CheckStatus::Pass, icon),
+                Some(icon) => Check::new(
+                    ICON_CHECK,
+                    CheckStatus::Fail,
+                    format!("{} does not exist", icon),
+                ),
+                None => Check::new(
+                    ICON_CHECK,
+                    CheckStatus::Warn,
+                    "no IconUri, a default icon is shown",
+                ),
+            };
+            checks.push(icon);
+        }
+        None => {
+            checks.push(Check::new(
+                REGISTRY_CHECK,
+                CheckStatus::Warn,
+                "not registered",
+            ));
+            checks.push(Check::new(ICON_CHECK, CheckStatus::Skip, "not registered"));
+        }
+    }
+    let shortcut = shortcuts
+        .iter()
+        .find(|shortcut| is_app_shortcut(shortcut, app_id));
+    let shortcut_check = match shortcut {
+        Some(shortcut) if shortcut.app_user_model_id.is_none() => Check::new(
+            SHORTCUT_CHECK,
+            CheckStatus::Warn,
+            format!("{} has no System.AppUserModel.ID", shortcut.path),
+        ),
+        Some(shortcut) => Check::new(SHORTCUT_CHECK, CheckStatus::Pass, shortcut.path.clone()),
+        None => Check::new(SHORTCUT_CHECK, CheckStatus::Skip, "no Start menu shortcut"),
+    };
+    checks.push(shortcut_check);
+    let attribution = match (registered.is_empty(), shortcut.is_some()) {
+        (false, _) => Check::new(ATTRIBUTION_CHECK, CheckStatus::Pass, "by registry entry"),
+        (true, true) => Check::new(
+            ATTRIBUTION_CHECK,
+            CheckStatus::Pass,
+            "by Start menu shortcut",
+        ),
+        (true, false) => Check::new(
+            ATTRIBUTION_CHECK,
+            CheckStatus::Fail,
+            "neither registered nor has a shortcut, Windows drops its toasts, run register",
+        ),
+    };
+    checks.push(attribution);
+    checks
+}
+
+/// Value of the `NotificationSetting` of the toast notifier.
+pub fn check_setting(setting: i32) -> Check {
+    let (status, detail) = match setting {
+        0 => (CheckStatus::Pass, "enabled"),
+        1 => (
+            CheckStatus::Fail,
+            "disabled for the application in notification settings",
+        ),
+        2 => (CheckStatus::Fail, "notifications are disabled for the user"),
+        3 => (CheckStatus::Fail, "disabled by group policy"),
+        4 => (CheckStatus::Fail, "disabled by the application manifest"),
+        _ => (CheckStatus::Warn, "unknown setting"),
+    };
+    Check::new(SETTING_CHECK, status, detail)
+}
+
+#[cfg(test)]
+use crate::registration::Scope;
+
+#[test]
+fn check_app_id_test() {
+    let status = |app_id: &str| check_app_id(app_id, false).status;
+    assert_eq!(status("com.company.app"), CheckStatus::Pass);
+    assert_eq!(status("com/app"), CheckStatus::Fail);
+    assert_eq!(status(""), CheckStatus::Fail);
+    assert_eq!(status(&"a".repeat(129)), CheckStatus::Fail);
+    assert_eq!(status("com.my app"), CheckStatus::Warn);
+    assert_eq!(status("a.b.c.d.e"), CheckStatus::Warn);
+    assert_eq!(status("com..app"), CheckStatus::Warn);
+    assert_eq!(check_app_id(r"C:\app.exe", true).status, CheckStatus::Skip);
+}
+
+#[test]
+fn check_registration_test() {
+    let app = RegisteredApp {
+        application_id: "com.app".into(),
+        scope: Scope::User,
+        display_name: Some("App".into()),
+        icon_uri: Some(r"C:\missing.png".into()),
+        icon_exists: false,
+        created_by_tool: true,
+    };
+    let shortcut = Shortcut {
+        path: r"C:\Start\tool.lnk".into(),
+        display_name: "tool".into(),
+        target: Some(r"C:\tool.exe".into()),
+        icon: None,
+        icon_exists: false,
+        app_user_model_id: None,
+        created_by_tool: true,
+    };
+    let summary = |app_id: &str| -> Vec<(String, CheckStatus)> {
+        check_registration(
+            std::slice::from_ref(&app),
+            std::slice::from_ref(&shortcut),
+            app_id,
+        )
+        .into_iter()
+        .map(|check| (check.name, check.status))
+        .collect()
+    };
+    let expected = |statuses: [CheckStatus; 4]| -> Vec<(String, CheckStatus)> {
+        [
+            REGISTRY_CHECK,
+            ICON_CHECK,
+            SHORTCUT_CHECK,
+            ATTRIBUTION_CHECK,
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .zip(statuses)
+        .collect()
+    };
+    use CheckStatus::*;
+    assert_eq!(summary("COM.APP"), expected([Pass, Fail, Skip, Pass]));
+    assert_eq!(summary(r"c:\tool.exe"), expected([Warn, Skip, Warn, Pass]));
+    assert_eq!(summary("other"), expected([Warn, Skip, Skip, Fail]));
+}
+
+#[test]
+fn report_test() {
+    let mut report = Report::new("com.app");
+    report.push(check_app_id("com.app", false));
+    report.push(check_setting(1));
+    report.push(check_setting(0));
+    assert!(!report.passed);
+    assert!(report.failed(SETTING_CHECK));
+    assert_eq!(
+        report.to_string(),
+        concat!(
+            "Doctor report for com.app\n",
+            "  [pass] app id: valid\n",
+            "  [FAIL] setting: disabled for the application in notification settings\n",
+            "  [pass] setting: enabled\n",
+            "Result: fail, 1 of 3 checks failed\n",
+        )
+    );
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["applicationId"], "com.app");
+    assert_eq!(json["checks"][1]["status"], "fail");
+}
@@ -0,0 +1,847 @@
+//! Numbered pub/sub log of events.
+//!
+//! [`event_log`] creates a [`Sender`] which publishes events into a shared buffer of the latest
+//! events and creates subscriptions. Publishing is O(1) regardless of the number of subscribers:
+//! every [`Subscriber`] only keeps its cursor, the number of the next event it reads, and reads
+//! events at its own pace. The buffer keeps events within a [`Retention`] by count, approximate
+//! size and age. Events older than the buffer can be kept by an [`EventPersistence`].
+
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+#[cfg(test)]
+use std::thread;
+#[cfg(test)]
+use std::time::Duration;
+use std::time::Instant;
+use tokio::sync::{watch, Notify};
+#[cfg(test)]
+use tokio::time::sleep;
+
+use crate::event_store::EventPersistence;
+use crate::ring_buffer::{ApproximateSize, Retention, RingBuffer};
+
+/// Default number of events kept in memory.
+pub const DEFAULT_RETENTION_COUNT: usize = 1000;
+
+/// Default number of events a subscriber may fall behind before its lag policy applies.
+pub const DEFAULT_SUBSCRIBER_QUEUE_LEN: usize = 1000;
+
+/// Number of persisted events read at once by a subscriber replaying from the store.
+const REPLAY_BATCH: usize = 256;
+
+/// Item received by a subscriber.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogItem<TEvent> {
+    /// Event with its number.
+    Event(usize, Arc<TEvent>),
+    /// Number of events the subscriber missed at this point of the stream.
+    Lagged(usize),
+    /// Subscription started before the oldest retained event.
+    Gap {
+        first_available: usize,
+        missed: usize,
+    },
+}
+
+/// First event of a subscription.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StartAt {
+    /// Event with the given number.
+    Number(usize),
+    /// The given number of latest events.
+    Tail(usize),
+}
+
+/// What happens when a subscriber falls behind by more than its queue length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LagPolicy {
+    /// Close the subscription.
+    Disconnect,
+    /// Skip the oldest unread events, subscriber receives `LogItem::Lagged` instead.
+    DropOldest,
+    /// Keep reading retained events, only events which are no longer retained are skipped.
+    Resume,
+}
+
+impl FromStr for LagPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "disconnect" => Ok(LagPolicy::Disconnect),
+            "drop-oldest" => Ok(LagPolicy::DropOldest),
+            "resume" => Ok(LagPolicy::Resume),
+            _ => Err(format!(
+                "[{}] is not a valid lag policy, expected disconnect, drop-oldest or resume",
+                s
+            )),
+        }
+    }
+}
+
+/// Delivery settings of one subscriber.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SubscriberOptions {
+    /// Number of unread events after which `lag_policy` applies.
+    pub queue_len: usize,
+    /// What happens when the subscriber falls behind.
+    pub lag_policy: LagPolicy,
+}
+
+impl Default for SubscriberOptions {
+    fn default() -> Self {
+        SubscriberOptions {
+            queue_len: DEFAULT_SUBSCRIBER_QUEUE_LEN,
+            lag_policy: LagPolicy::Resume,
+        }
+    }
+}
+
+/// Retained events read by [`Sender::read_range`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventPage<TEvent> {
+    /// Events matching the filter, oldest first.
+    pub events: Vec<(usize, Arc<TEvent>)>,
+    /// Number of the first retained event at or after `from`, a larger one means events were lost.
+    pub first_available: usize,
+    /// Number of the first event which was not read, `from` of the next page.
+    pub next: usize,
+}
+
+/// Returned when publishing to a closed log, holds the event.
+#[derive(PartialEq)]
+pub struct SendError<TEvent>(pub TEvent);
+
+impl<TEvent> Debug for SendError<TEvent> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SendError(..)")
+    }
+}
+
+/// State shared by senders and subscribers of one log.
+struct Shared<TEvent> {
+    buffer: RwLock<RingBuffer<Arc<TEvent>>>,
+    /// Approximate size of an event for the retention by bytes.
+    size_of: fn(&TEvent) -> usize,
+    /// Locked while an event is numbered, stored and pushed, so numbers follow publish order.
+    store: Mutex<Option<Box<dyn EventPersistence<TEvent>>>>,
+    /// Number of the next event, changes wake up waiting subscribers.
+    next_number: watch::Sender<usize>,
+    closed: AtomicBool,
+}
+
+impl<TEvent> Shared<TEvent> {
+    fn publish(&self, evt: TEvent) -> Result<(), SendError<TEvent>> {
+        let mut store = match self.store.lock() {
+            Ok(store) => store,
+            Err(_) => return Err(SendError(evt)),
+        };
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SendError(evt));
+        }
+        let mut buffer = match self.buffer.write() {
+            Ok(buffer) => buffer,
+            Err(_) => return Err(SendError(evt)),
+        };
+        let number = buffer.get_next_number();
+        if let Some(store) = store.as_mut() {
+            if let Err(e) = store.append(number, &evt) {
+                eprintln!("Failed to persist event {}: {}", number, e);
+            }
+        }
+        let size = (self.size_of)(&evt);
+        buffer.push_sized(Arc::new(evt), size, Instant::now());
+        drop(buffer);
+        drop(store);
+        self.next_number.send_replace(number + 1);
+        Ok(())
+    }
+
+    /// Evicts events older than the maximum age, so the base index seen by readers is current.
+    fn evict_expired(&self) {
+        let now = Instant::now();
+        let expired = match self.buffer.read() {
+            Ok(buffer) => buffer.has_expired(now),
+            Err(_) => false,
+        };
+        if expired {
+            if let Ok(mut buffer) = self.buffer.write() {
+                buffer.evict(now);
+            }
+        }
+    }
+
+    /// Stops publishing, subscribers receive remaining events and then `None`.
+    fn close(&self) {
+        let store = self.store.lock().ok().and_then(|mut store| {
+            self.closed.store(true, Ordering::Release);
+            store.take()
+        });
+        drop(store);
+        self.next_number.send_modify(|_| {});
+    }
+
+    fn read_store(&self, from: usize, to: usize) -> Vec<(usize, TEvent)> {
+        let store = match self.store.lock() {
+            Ok(store) => store,
+            Err(_) => return vec![],
+        };
+        match store.as_ref().map(|store| store.read_range(from, to)) {
+            Some(Ok(events)) => events,
+            Some(Err(e)) => {
+                eprintln!("Failed to read persisted events: {}", e);
+                vec![]
+            }
+            None => vec![],
+        }
+    }
+}
+
+/// Closes the log when the last sender is dropped.
+struct Publisher<TEvent>(Arc<Shared<TEvent>>);
+
+impl<TEvent> Drop for Publisher<TEvent> {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+/// Publishes events and creates subscriptions, can be cloned freely.
+/// The log is closed when all senders are dropped.
+pub struct Sender<TEvent> {
+    publisher: Arc<Publisher<TEvent>>,
+    subscriber_options: SubscriberOptions,
+}
+
+impl<TEvent> Clone for Sender<TEvent> {
+    fn clone(&self) -> Self {
+        Sender {
+            publisher: self.publisher.clone(),
+            subscriber_options: self.subscriber_options,
+        }
+    }
+}
+
+impl<TEvent> Sender<TEvent> {
+    /// Publishes event, it never waits for subscribers.
+    pub fn blocking_send(&self, item: TEvent) -> Result<(), SendError<TEvent>> {
+        self.publisher.0.publish(item)
+    }
+
+    /// Publishes event, it never waits for subscribers.
+    pub async fn send(&self, item: TEvent) -> Result<(), SendError<TEvent>> {
+        self.publisher.0.publish(item)
+    }
+
+    /// Options used by `subscribe` and `subscribe_from`.
+    pub fn with_subscriber_options(mut self, options: SubscriberOptions) -> Self {
+        self.subscriber_options = options;
+        self
+    }
+
+    /// Subscribes to all retained and future events.
+    pub async fn subscribe(&self) -> Subscriber<TEvent> {
+        self.subscribe_from(0).await
+    }
+
+    /// Subscribes to events with number `from` and later, retained events are replayed first.
+    pub async fn subscribe_from(&self, from: usize) -> Subscriber<TEvent> {
+        self.subscribe_with(StartAt::Number(from), self.subscriber_options)
+            .await
+    }
+
+    /// Subscribes to the last `count` events and later ones.
+    pub async fn subscribe_tail(&self, count: usize) -> Subscriber<TEvent> {
+        self.subscribe_with(StartAt::Tail(count), self.subscriber_options)
+            .await
+    }
+
+    /// Reads up to `limit` retained events matching `filter`, starting at `start` and ending
+    /// before `to` or the next published event, without subscribing.
+    pub fn read_range(
+        &self,
+        start: StartAt,
+        to: Option<usize>,
+        limit: usize,
+        filter: impl Fn(&TEvent) -> bool,
+    ) -> EventPage<TEvent> {
+        let shared = &self.publisher.0;
+        shared.evict_expired();
+        let store = shared.store.lock().unwrap_or_else(|e| e.into_inner());
+        let buffer = shared.buffer.read().unwrap_or_else(|e| e.into_inner());
+        let next_number = buffer.get_next_number();
+        let base_index = buffer.get_base_index();
+        let from = match start {
+            StartAt::Number(from) => from,
+            StartAt::Tail(count) => next_number.saturating_sub(count),
+        };
+        let end = to.unwrap_or(next_number).min(next_number);
+        let mut page = EventPage {
+            events: vec![],
+            first_available: from.max(base_index),
+            next: from,
+        };
+        let mut persisted = vec![];
+        if let Some(store) = store.as_ref().filter(|_| from < base_index.min(end)) {
+            match store.read_range(from, base_index.min(end)) {
+                Ok(events) => persisted = events,
+                Err(e) => eprintln!("Failed to read persisted events: {}", e),
+            }
+        }
+        if let Some((number, _)) = persisted.first() {
+            page.first_available = *number;
+        }
+        let persisted = persisted
+            .into_iter()
+            .filter(|(number, _)| *number >= from)
+            .map(|(number, evt)| (number, Arc::new(evt)));
+        let buffered = (from.max(base_index)..end)
+            .filter_map(|number| buffer.get(number).map(|evt| (number, evt.clone())));
+        for (number, evt) in persisted.chain(buffered) {
+            if page.events.len() >= limit {
+                break;
+            }
+            page.next = number + 1;
+            if filter(&evt) {
+                page.events.push((number, evt));
+            }
+        }
+        if page.events.len() < limit {
+            page.next = page.next.max(end);
+        }
+        page
+    }
+
+    /// Subscribes with options other than the sender defaults.
+    pub async fn subscribe_with(
+        &self,
+        start: StartAt,
+        options: SubscriberOptions,
+    ) -> Subscriber<TEvent> {
+        let shared = self.publisher.0.clone();
+        shared.evict_expired();
+        let (cursor, base_index) = {
+            let buffer = shared.buffer.read().unwrap_or_else(|e| e.into_inner());
+            let cursor = match start {
+                StartAt::Number(from) => from,
+                StartAt::Tail(count) => buffer.get_next_number().saturating_sub(count),
+            };
+            (cursor, buffer.get_base_index())
+        };
+        Subscriber {
+            next_number: shared.next_number.subscribe(),
+            shared,
+            handle: Arc::new(SubscriberHandle {
+                closed: AtomicBool::new(false),
+                notify: Notify::new(),
+            }),
+            options,
+            cursor,
+            started: cursor >= base_index,
+            replay: VecDeque::new(),
+            lag_disconnected: false,
+        }
+    }
+}
+
+struct SubscriberHandle {
+    closed: AtomicBool,
+    notify: Notify,
+}
+
+enum Next<TEvent> {
+    Item(LogItem<TEvent>),
+    Wait,
+    Closed,
+}
+
+/// Receiving side of a subscription.
+pub struct Subscriber<TEvent> {
+    shared: Arc<Shared<TEvent>>,
+    next_number: watch::Receiver<usize>,
+    handle: Arc<SubscriberHandle>,
+    options: SubscriberOptions,
+    /// Number of the next event to read.
+    cursor: usize,
+    /// Whether missing events are reported as lag instead of a gap at the start.
+    started: bool,
+    /// Persisted events read ahead from the store.
+    replay: VecDeque<(usize, Arc<TEvent>)>,
+    /// Closed by [`LagPolicy::Disconnect`].
+    lag_disconnected: bool,
+}
+
+impl<TEvent> Subscriber<TEvent> {
+    /// Next item of the subscription, None when it was closed.
+    pub async fn recv(&mut self) -> Option<LogItem<TEvent>> {
+        loop {
+            self.next_number.borrow_and_update();
+            match self.next() {
+                Next::Item(item) => return Some(item),
+                Next::Closed => return None,
+                Next::Wait => {}
+            }
+            tokio::select! {
+                _ = self.next_number.changed() => {}
+                _ = self.handle.notify.notified() => {}
+            }
+        }
+    }
+
+    /// Subscription was closed because it fell behind with [`LagPolicy::Disconnect`].
+    pub fn is_lag_disconnected(&self) -> bool {
+        self.lag_disconnected
+    }
+
+    fn next(&mut self) -> Next<TEvent> {
+        if self.handle.closed.load(Ordering::Acquire) {
+            return Next::Closed;
+        }
+        if let Some((number, evt)) = self.replay.pop_front() {
+            self.cursor = number + 1;
+            self.started = true;
+            return Next::Item(LogItem::Event(number, evt));
+        }
+        self.shared.evict_expired();
+        let buffer = match self.shared.buffer.read() {
+            Ok(buffer) => buffer,
+            Err(_) => return Next::Closed,
+        };
+        let next_number = buffer.get_next_number();
+        let base_index = buffer.get_base_index();
+        let queue_len = self.options.queue_len.max(1);
+        let behind = next_number.saturating_sub(self.cursor);
+        if behind > queue_len {
+            match self.options.lag_policy {
+                LagPolicy::Disconnect => {
+                    self.lag_disconnected = true;
+                    self.handle.closed.store(true, Ordering::Release);
+                    return Next::Closed;
+                }
+                LagPolicy::DropOldest => {
+                    let skipped = behind - queue_len;
+                    self.cursor += skipped;
+                    self.started = true;
+                    return Next::Item(LogItem::Lagged(skipped));
+                }
+                LagPolicy::Resume => {}
+            }
+        }
+        if self.cursor >= next_number {
+            return match self.shared.closed.load(Ordering::Acquire) {
+                true => Next::Closed,
+                false => Next::Wait,
+            };
+        }
+        if let Some(evt) = buffer.get(self.cursor) {
+            let number = self.cursor;
+            self.cursor += 1;
+            self.started = true;
+            return Next::Item(LogItem::Event(number, evt.clone()));
+        }
+        drop(buffer);
+        let to = base_index.min(self.cursor + REPLAY_BATCH);
+        self.replay.extend(
+            self.shared
+                .read_store(self.cursor, to)
+                .into_iter()
+                .filter(|(number, _)| *number >= self.cursor)
+                .map(|(number, evt)| (number, Arc::new(evt))),
+        );
+        let first_available = self
+            .replay
+            .front()
+            .map_or(base_index, |(number, _)| *number);
+        if first_available == self.cursor {
+            return self.next();
+        }
+        let missed = first_available - self.cursor;
+        self.cursor = first_available;
+        let item = match self.started {
+            true => LogItem::Lagged(missed),
+            false => LogItem::Gap {
+                first_available,
+                missed,
+            },
+        };
+        self.started = true;
+        Next::Item(item)
+    }
+
+    /// Ends the subscription.
+    pub async fn drop_async(&self) {
+        self.get_unsubscriber().unsubscribe_async().await
+    }
+    /// Handle which ends the subscription while the subscriber is moved elsewhere.
+    pub fn get_unsubscriber(&self) -> Unsubscriber {
+        Unsubscriber {
+            handle: self.handle.clone(),
+        }
+    }
+}
+
+/// Ends a subscription, see [`Subscriber::get_unsubscriber`].
+pub struct Unsubscriber {
+    handle: Arc<SubscriberHandle>,
+}
+
+impl Unsubscriber {
+    /// Ends the subscription, its pending `recv` returns `None`.
+    pub async fn unsubscribe_async(&self) {
+        self.handle.closed.store(true, Ordering::Release);
+        self.handle.notify.notify_one();
+    }
+}
+
+/// Creates event log which keeps the latest `buffer_len` events in memory.
+pub fn event_log<TEvent>(buffer_len: usize) -> Sender<TEvent> {
+    create(
+        RingBuffer::new(buffer_len),
+        |_| std::mem::size_of::<TEvent>(),
+        None,
+    )
+}
+
+/// Creates event log which keeps events within the retention in memory and, when the store is
+/// given, persists events to it and replays them after restart.
+pub fn event_log_with_retention<TEvent: ApproximateSize>(
+    retention: Retention,
+    store: Option<Box<dyn EventPersistence<TEvent>>>,
+) -> Sender<TEvent> {
+    let buffer = match &store {
+        Some(store) => load_buffer(retention, store.as_ref(), TEvent::approximate_size),
+        None => RingBuffer::with_retention(retention, 0),
+    };
+    create(buffer, TEvent::approximate_size, store)
+}
+
+/// Creates event log which persists events to the store and replays them after restart.
+/// Numbering continues after the last stored event and the latest events are loaded into memory.
+pub fn event_log_with_store<TEvent>(
+    buffer_len: usize,
+    store: Box<dyn EventPersistence<TEvent>>,
+) -> Sender<TEvent> {
+    let size_of = |_: &TEvent| std::mem::size_of::<TEvent>();
+    let buffer = load_buffer(Retention::count(buffer_len), store.as_ref(), size_of);
+    create(buffer, size_of, Some(store))
+}
+
+/// Loads the latest persisted events within the retention, their age counts from now.
+fn load_buffer<TEvent>(
+    retention: Retention,
+    store: &dyn EventPersistence<TEvent>,
+    size_of: fn(&TEvent) -> usize,
+) -> RingBuffer<Arc<TEvent>> {
+    let next_number = store.next_number();
+    let events = store
+        .read_range(next_number.saturating_sub(retention.max_count), next_number)
+        .unwrap_or_else(|e| {
+            eprintln!("Failed to load persisted events: {}", e);
+            vec![]
+        });
+    let first_number = events.first().map(|(num, _)| *num).unwrap_or(next_number);
+    let mut buffer = RingBuffer::with_retention(retention, first_number);
+    let now = Instant::now();
+    for (_, evt) in events {
+        let size = size_of(&evt);
+        buffer.push_sized(Arc::new(evt), size, now);
+    }
+    buffer
+}
+
+fn create<TEvent>(
+    buffer: RingBuffer<Arc<TEvent>>,
+    size_of: fn(&TEvent) -> usize,
+    store: Option<Box<dyn EventPersistence<TEvent>>>,
+) -> Sender<TEvent> {
+    let (next_number, _) = watch::channel(buffer.get_next_number());
+    let shared = Arc::new(Shared {
+        buffer: RwLock::new(buffer),
+        size_of,
+        store: Mutex::new(store),
+        next_number,
+        closed: AtomicBool::new(false),
+    });
+    Sender {
+        publisher: Arc::new(Publisher(shared)),
+        subscriber_options: SubscriberOptions::default(),
+    }
+}
+
+#[cfg(test)]
+fn event<TEvent>(number: usize, evt: TEvent) -> Option<LogItem<TEvent>> {
+    Some(LogItem::Event(number, Arc::new(evt)))
+}
+
+#[tokio::test]
+async fn main_test() {
+    let s = event_log::<String>(10);
+    let sender = s.clone();
+    thread::spawn(move || {
+        sender.blocking_send("Hello before subs".into()).unwrap();
+    });
+    let mut subscription = s.subscribe().await;
+    tokio::spawn(async move {
+        let mut counter = 3;
+        while let Some(item) = subscription.recv().await {
+            counter -= 1;
+            if counter == 0 {
+                break;
+            }
+            println!("SUB1: {:?}", item)
+        }
+        subscription.drop_async().await;
+        println!("Ended")
+    });
+    let sender = s.clone();
+    thread::spawn(move || {
+        for i in 1..5 {
+            sender.blocking_send(format!("Hello after {}", i)).unwrap();
+        }
+    });
+    sleep(Duration::from_secs(1)).await;
+    let mut subscription2 = s.subscribe().await;
+    sleep(Duration::from_secs(1)).await;
+    let unsubscriber = subscription2.get_unsubscriber();
+    tokio::spawn(async move {
+        while let Some(item) = subscription2.recv().await {
+            println!("SUB2: {:?}", item);
+        }
+    });
+    sleep(Duration::from_secs(1)).await;
+    unsubscriber.unsubscribe_async().await;
+}
+
+#[tokio::test]
+async fn persisted_event_log_test() {
+    use crate::event_store::{EventStoreConfig, FsyncPolicy, SegmentStore};
+    let config = EventStoreConfig {
+        dir: std::env::temp_dir().join(format!("event-log-{}", uuid::Uuid::new_v4())),
+        fsync: FsyncPolicy::Always,
+        segment_size: crate::event_store::DEFAULT_SEGMENT_SIZE,
+        max_segments: crate::event_store::DEFAULT_MAX_SEGMENTS,
+    };
+    let store = SegmentStore::<String>::open(config.clone()).unwrap();
+    let s = event_log_with_store::<String>(2, Box::new(store));
+    for i in 0..5 {
+        s.send(format!("first run {}", i)).await.unwrap();
+    }
+    let mut subscription = s.subscribe_from(4).await;
+    assert_eq!(
+        subscription.recv().await,
+        event(4, "first run 4".to_string())
+    );
+    drop(s);
+    assert_eq!(subscription.recv().await, None);
+
+    let store = SegmentStore::<String>::open(config.clone()).unwrap();
+    let s = event_log_with_store::<String>(2, Box::new(store));
+    s.send("second run".to_string()).await.unwrap();
+    let mut subscription = s.subscribe_from(1).await;
+    let mut received = vec![];
+    for _ in 0..5 {
+        received.push(subscription.recv().await.unwrap());
+    }
+    assert_eq!(
+        received,
+        vec![
+            LogItem::Event(1, Arc::new("first run 1".to_string())),
+            LogItem::Event(2, Arc::new("first run 2".to_string())),
+            LogItem::Event(3, Arc::new("first run 3".to_string())),
+            LogItem::Event(4, Arc::new("first run 4".to_string())),
+            LogItem::Event(5, Arc::new("second run".to_string())),
+        ]
+    );
+    std::fs::remove_dir_all(config.dir).unwrap();
+}
+
+#[cfg(test)]
+async fn assert_fast_subscriber_receives_all(policy: LagPolicy) -> Subscriber<usize> {
+    let s = event_log::<usize>(1000);
+    let options = SubscriberOptions {
+        queue_len: 2,
+        lag_policy: policy,
+    };
+    let stuck = s.subscribe_with(StartAt::Number(0), options).await;
+    let mut fast = s.subscribe_from(0).await;
+    let reader = tokio::spawn(async move {
+        let mut received = vec![];
+        while received.len() < 100 {
+            match fast.recv().await {
+                Some(LogItem::Event(num, _)) => received.push(num),
+                item => panic!("unexpected {:?}", item),
+            }
+        }
+        received
+    });
+    for i in 0..100 {
+        s.send(i).await.unwrap();
+    }
+    let received = tokio::time::timeout(Duration::from_secs(5), reader)
+        .await
+        .expect("stuck subscriber blocked delivery")
+        .unwrap();
+    assert_eq!(received, (0..100).collect::<Vec<_>>());
+    stuck
+}
+
+#[tokio::test]
+async fn drop_oldest_lag_policy_test() {
+    let mut stuck = assert_fast_subscriber_receives_all(LagPolicy::DropOldest).await;
+    assert_eq!(stuck.recv().await, Some(LogItem::Lagged(98)));
+    assert_eq!(stuck.recv().await, event(98, 98));
+    assert_eq!(stuck.recv().await, event(99, 99));
+}
+
+#[tokio::test]
+async fn disconnect_lag_policy_test() {
+    let mut stuck = assert_fast_subscriber_receives_all(LagPolicy::Disconnect).await;
+    assert!(!stuck.is_lag_disconnected());
+    assert_eq!(stuck.recv().await, None);
+    assert!(stuck.is_lag_disconnected());
+}
+
+#[tokio::test]
+async fn resume_lag_policy_test() {
+    let mut stuck = assert_fast_subscriber_receives_all(LagPolicy::Resume).await;
+    for i in 0..100 {
+        assert_eq!(stuck.recv().await, event(i, i));
+    }
+}
+
+#[tokio::test]
+async fn resume_after_buffer_wrapped_test() {
+    let s = event_log::<usize>(10);
+    let options = SubscriberOptions {
+        queue_len: 2,
+        lag_policy: LagPolicy::Resume,
+    };
+    let mut stuck = s.subscribe_with(StartAt::Number(0), options).await;
+    for i in 0..30 {
+        s.send(i).await.unwrap();
+    }
+    let mut fast = s.subscribe_from(29).await;
+    assert_eq!(fast.recv().await, event(29, 29));
+    assert_eq!(stuck.recv().await, Some(LogItem::Lagged(20)));
+    for i in 20..30 {
+        assert_eq!(stuck.recv().await, event(i, i));
+    }
+}
+
+#[tokio::test]
+async fn gap_and_tail_test() {
+    let s = event_log::<usize>(10);
+    for i in 0..30 {
+        s.send(i).await.unwrap();
+    }
+    let mut subscription = s.subscribe_from(5).await;
+    assert_eq!(
+        subscription.recv().await,
+        Some(LogItem::Gap {
+            first_available: 20,
+            missed: 15
+        })
+    );
+    assert_eq!(subscription.recv().await, event(20, 20));
+    let mut subscription = s.subscribe_from(25).await;
+    assert_eq!(subscription.recv().await, event(25, 25));
+    let mut subscription = s.subscribe_tail(3).await;
+    assert_eq!(subscription.recv().await, event(27, 27));
+    let mut subscription = s.subscribe_tail(100).await;
+    assert_eq!(
+        subscription.recv().await,
+        Some(LogItem::Gap {
+            first_available: 20,
+            missed: 20
+        })
+    );
+}
+
+#[tokio::test]
+async fn retention_test() {
+    let retention = Retention {
+        max_bytes: Some(100),
+        max_age: Some(Duration::from_millis(200)),
+        ..Retention::count(10)
+    };
+    let s = event_log_with_retention::<String>(retention, None);
+    for i in 0..5 {
+        s.send("x".repeat(10 + i)).await.unwrap();
+    }
+    // Every event takes size_of::<String>() and its length, only the latest ones fit in 100 bytes.
+    let mut subscription = s.subscribe_from(0).await;
+    let first_available = match subscription.recv().await {
+        Some(LogItem::Gap {
+            first_available, ..
+        }) => first_available,
+        item => panic!("unexpected {:?}", item),
+    };
+    assert!((1..4).contains(&first_available));
+    assert_eq!(
+        subscription.recv().await,
+        event(first_available, "x".repeat(10 + first_available))
+    );
+    sleep(Duration::from_millis(300)).await;
+    s.send("late".into()).await.unwrap();
+    assert_eq!(
+        subscription.recv().await,
+        Some(LogItem::Lagged(4 - first_available))
+    );
+    assert_eq!(subscription.recv().await, event(5, "late".to_string()));
+    sleep(Duration::from_millis(300)).await;
+    let page = s.read_range(StartAt::Number(0), None, 10, |_| true);
+    assert!(page.events.is_empty());
+    assert_eq!((page.first_available, page.next), (6, 6));
+}
+
+#[tokio::test]
+async fn read_range_test() {
+    let s = event_log::<usize>(10);
+    for i in 0..30 {
+        s.send(i).await.unwrap();
+    }
+    let page = s.read_range(StartAt::Number(5), None, 3, |_| true);
+    assert_eq!(page.first_available, 20);
+    assert_eq!(
+        page.events,
+        vec![(20, Arc::new(20)), (21, Arc::new(21)), (22, Arc::new(22))]
+    );
+    assert_eq!(page.next, 23);
+    let page = s.read_range(StartAt::Number(page.next), Some(28), 100, |x| x % 2 == 0);
+    assert_eq!(page.events, vec![(24, Arc::new(24)), (26, Arc::new(26))]);
+    assert_eq!(page.next, 28);
+    let page = s.read_range(StartAt::Tail(2), None, 100, |_| true);
+    assert_eq!(page.events, vec![(28, Arc::new(28)), (29, Arc::new(29))]);
+    assert_eq!(page.next, 30);
+    let page = s.read_range(StartAt::Number(30), None, 100, |_| true);
+    assert_eq!(page.events, vec![]);
+    assert_eq!(page.next, 30);
+}
+
+#[tokio::test]
+async fn read_range_from_store_test() {
+    use crate::event_store::{EventStoreConfig, FsyncPolicy, SegmentStore};
+    let config = EventStoreConfig {
+        dir: std::env::temp_dir().join(format!("event-log-{}", uuid::Uuid::new_v4())),
+        fsync: FsyncPolicy::Never,
+        segment_size: crate::event_store::DEFAULT_SEGMENT_SIZE,
+        max_segments: crate::event_store::DEFAULT_MAX_SEGMENTS,
+    };
+    let store = SegmentStore::<usize>::open(config.clone()).unwrap();
+    let s = event_log_with_store::<usize>(2, Box::new(store));
+    for i in 0..6 {
+        s.send(i).await.unwrap();
+    }
+    let page = s.read_range(StartAt::Number(1), None, 4, |_| true);
+    assert_eq!(page.first_available, 1);
+    assert_eq!(
+        page.events.iter().map(|(n, _)| *n).collect::<Vec<_>>(),
+        vec![1, 2, 3, 4]
+    );
+    assert_eq!(page.next, 5);
+    drop(s);
+    std::fs::remove_dir_all(config.dir).unwrap();
+}
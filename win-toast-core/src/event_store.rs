@@ -0,0 +1,451 @@
+//! Segment files persisting event log events.
+//!
+//! Events are appended as JSON lines to `{first number}.log` files with an `.idx` file of line
+//! offsets next to each, a torn last line is truncated when the store is opened. Other lines which
+//! cannot be read fail opening the store.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Default size in bytes of one segment file.
+pub const DEFAULT_SEGMENT_SIZE: u64 = 1024 * 1024;
+/// Default number of retained segment files.
+pub const DEFAULT_MAX_SEGMENTS: usize = 16;
+
+const LOG_EXTENSION: &str = "log";
+const INDEX_EXTENSION: &str = "idx";
+const INDEX_ENTRY_SIZE: u64 = 8;
+
+/// Storage for events which outlive the in-memory buffer of the event log.
+pub trait EventPersistence<TEvent>: Send {
+    /// Number which will be assigned to the next appended event.
+    fn next_number(&self) -> usize;
+    /// Stores event with the given number.
+    fn append(&mut self, number: usize, event: &TEvent) -> std::io::Result<()>;
+    /// Retained events with numbers in `from..to`, oldest first.
+    fn read_range(&self, from: usize, to: usize) -> std::io::Result<Vec<(usize, TEvent)>>;
+}
+
+/// When appended events are synced to disk.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FsyncPolicy {
+    /// Sync after each appended event.
+    Always,
+    /// Sync after every N appended events.
+    Every(u32),
+    /// Leave syncing to the OS.
+    Never,
+}
+
+impl FromStr for FsyncPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(FsyncPolicy::Always),
+            "never" => Ok(FsyncPolicy::Never),
+            n => match n.parse::<u32>() {
+                Ok(0) | Err(_) => Err(format!(
+                    "[{}] is not a valid fsync policy, expected always, never or number of events",
+                    s
+                )),
+                Ok(n) => Ok(FsyncPolicy::Every(n)),
+            },
+        }
+    }
+}
+
+/// Location and retention of a [`SegmentStore`].
+#[derive(Debug, Clone)]
+pub struct EventStoreConfig {
+    /// Directory with segment files, created when missing.
+    pub dir: PathBuf,
+    /// When appended events are synced to disk.
+    pub fsync: FsyncPolicy,
+    /// Size in bytes after which a new segment is started.
+    pub segment_size: u64,
+    /// Number of segments kept, older ones are deleted.
+    pub max_segments: usize,
+}
+
+#[derive(Serialize)]
+struct StoredEventRef<'a, TEvent> {
+    n: usize,
+    e: &'a TEvent,
+}
+
+#[derive(Deserialize)]
+struct StoredEvent<TEvent> {
+    n: usize,
+    e: TEvent,
+}
+
+#[derive(Debug, Clone)]
+struct Segment {
+    first: usize,
+    count: usize,
+    bytes: u64,
+}
+
+struct SegmentWriter {
+    log: File,
+    index: File,
+}
+
+/// Append-only JSON lines segments with an index of line offsets.
+///
+/// Segment `<first number>.log` holds one `{"n": number, "e": event}` line per event,
+/// `<first number>.idx` holds little endian u64 offset of each line.
+pub struct SegmentStore<TEvent> {
+    config: EventStoreConfig,
+    segments: Vec<Segment>,
+    writer: Option<SegmentWriter>,
+    next_number: usize,
+    unsynced: u32,
+    _event: PhantomData<fn() -> TEvent>,
+}
+
+impl<TEvent> SegmentStore<TEvent>
+where
+    TEvent: Serialize + DeserializeOwned,
+{
+    /// Opens the store, recovering the last segment after an unclean shutdown.
+    pub fn open(config: EventStoreConfig) -> std::io::Result<SegmentStore<TEvent>> {
+        fs::create_dir_all(&config.dir)?;
+        let mut firsts = vec![];
+        for entry in fs::read_dir(&config.dir)? {
+            let path = entry?.path();
+            if path.extension().map(|x| x == LOG_EXTENSION) != Some(true) {
+                continue;
+            }
+            if let Some(first) = path
+                .file_stem()
+                .and_then(|x| x.to_str())
+                .and_then(|x| x.parse::<usize>().ok())
+            {
+                firsts.push(first);
+            }
+        }
+        firsts.sort();
+        let mut segments = vec![];
+        for (position, first) in firsts.iter().enumerate() {
+            let segment = if position + 1 == firsts.len() {
+                recover_segment::<TEvent>(&config.dir, *first)?
+            } else {
+                Segment {
+                    first: *first,
+                    count: (fs::metadata(segment_path(&config.dir, *first, INDEX_EXTENSION))?.len()
+                        / INDEX_ENTRY_SIZE) as usize,
+                    bytes: fs::metadata(segment_path(&config.dir, *first, LOG_EXTENSION))?.len(),
+                }
+            };
+            segments.push(segment);
+        }
+        let next_number = segments.last().map(|x| x.first + x.count).unwrap_or(0);
+        Ok(SegmentStore {
+            config,
+            segments,
+            writer: None,
+            next_number,
+            unsynced: 0,
+            _event: PhantomData,
+        })
+    }
+
+    fn start_segment(&mut self, first: usize) -> std::io::Result<()> {
+        self.sync()?;
+        self.writer = None;
+        self.segments.push(Segment {
+            first,
+            count: 0,
+            bytes: 0,
+        });
+        while self.segments.len() > self.config.max_segments.max(1) {
+            let removed = self.segments.remove(0);
+            fs::remove_file(segment_path(&self.config.dir, removed.first, LOG_EXTENSION))?;
+            fs::remove_file(segment_path(
+                &self.config.dir,
+                removed.first,
+                INDEX_EXTENSION,
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn open_writer(&mut self) -> std::io::Result<&mut SegmentWriter> {
+        if self.writer.is_none() {
+            let first = self.segments.last().map(|x| x.first).unwrap_or(0);
+            let open = |extension: &str| {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(segment_path(&self.config.dir, first, extension))
+            };
+            self.writer = Some(SegmentWriter {
+                log: open(LOG_EXTENSION)?,
+                index: open(INDEX_EXTENSION)?,
+            });
+        }
+        Ok(self.writer.as_mut().unwrap())
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        if self.unsynced > 0 {
+            if let Some(writer) = self.writer.as_mut() {
+                writer.log.sync_data()?;
+                writer.index.sync_data()?;
+            }
+            self.unsynced = 0;
+        }
+        Ok(())
+    }
+
+    fn read_segment(
+        &self,
+        segment: &Segment,
+        from: usize,
+        to: usize,
+    ) -> std::io::Result<Vec<(usize, TEvent)>> {
+        let start = from.max(segment.first);
+        let end = to.min(segment.first + segment.count);
+        if start >= end {
+            return Ok(vec![]);
+        }
+        let mut index = File::open(segment_path(
+            &self.config.dir,
+            segment.first,
+            INDEX_EXTENSION,
+        ))?;
+        index.seek(SeekFrom::Start(
+            (start - segment.first) as u64 * INDEX_ENTRY_SIZE,
+        ))?;
+        let mut offset = [0u8; INDEX_ENTRY_SIZE as usize];
+        index.read_exact(&mut offset)?;
+        let mut log = File::open(segment_path(&self.config.dir, segment.first, LOG_EXTENSION))?;
+        log.seek(SeekFrom::Start(u64::from_le_bytes(offset)))?;
+        let mut events = Vec::with_capacity(end - start);
+        for line in BufReader::new(log).lines().take(end - start) {
+            let stored: StoredEvent<TEvent> = serde_json::from_str(&line?)?;
+            events.push((stored.n, stored.e));
+        }
+        Ok(events)
+    }
+}
+
+impl<TEvent> EventPersistence<TEvent> for SegmentStore<TEvent>
+where
+    TEvent: Serialize + DeserializeOwned,
+{
+    fn next_number(&self) -> usize {
+        self.next_number
+    }
+
+    fn append(&mut self, number: usize, event: &TEvent) -> std::io::Result<()> {
+        if number != self.next_number {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("expected event {}, got {}", self.next_number, number),
+            ));
+        }
+        let mut line = serde_json::to_vec(&StoredEventRef {
+            n: number,
+            e: event,
+        })?;
+        line.push(b'\n');
+        let full = self
+            .segments
+            .last()
+            .map(|x| x.count > 0 && x.bytes + line.len() as u64 > self.config.segment_size)
+            .unwrap_or(true);
+        if full {
+            self.start_segment(number)?;
+        }
+        let offset = self.segments.last().map(|x| x.bytes).unwrap_or(0);
+        let writer = self.open_writer()?;
+        writer.log.write_all(&line)?;
+        writer.index.write_all(&offset.to_le_bytes())?;
+        if let Some(segment) = self.segments.last_mut() {
+            segment.count += 1;
+            segment.bytes += line.len() as u64;
+        }
+        self.next_number += 1;
+        self.unsynced += 1;
+        match self.config.fsync {
+            FsyncPolicy::Always => self.sync()?,
+            FsyncPolicy::Every(n) if self.unsynced >= n => self.sync()?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn read_range(&self, from: usize, to: usize) -> std::io::Result<Vec<(usize, TEvent)>> {
+        let mut events = vec![];
+        for segment in self.segments.iter() {
+            events.extend(self.read_segment(segment, from, to)?);
+        }
+        Ok(events)
+    }
+}
+
+impl<TEvent> Drop for SegmentStore<TEvent> {
+    fn drop(&mut self) {
+        if let Some(writer) = self.writer.as_mut() {
+            _ = writer.log.sync_data();
+            _ = writer.index.sync_data();
+        }
+    }
+}
+
+fn segment_path(dir: &Path, first: usize, extension: &str) -> PathBuf {
+    dir.join(format!("{:020}.{}", first, extension))
+}
+
+/// Drops a torn last line of the segment, which has no newline yet, and rebuilds its index.
+/// Complete lines which cannot be read fail the recovery instead of dropping the events after them.
+fn recover_segment<TEvent: DeserializeOwned>(dir: &Path, first: usize) -> std::io::Result<Segment> {
+    let log_path = segment_path(dir, first, LOG_EXTENSION);
+    let content = fs::read(&log_path)?;
+    let mut offsets: Vec<u64> = vec![];
+    let mut position = 0;
+    while let Some(length) = content[position..].iter().position(|x| *x == b'\n') {
+        let line = &content[position..position + length];
+        match serde_json::from_slice::<StoredEvent<TEvent>>(line) {
+            Ok(stored) if stored.n == first + offsets.len() => offsets.push(position as u64),
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "Event {} in {} is corrupted",
+                        first + offsets.len(),
+                        log_path.display()
+                    ),
+                ))
+            }
+        }
+        position += length + 1;
+    }
+    if position < content.len() {
+        OpenOptions::new()
+            .write(true)
+            .open(&log_path)?
+            .set_len(position as u64)?;
+    }
+    let index: Vec<u8> = offsets.iter().flat_map(|x| x.to_le_bytes()).collect();
+    fs::write(segment_path(dir, first, INDEX_EXTENSION), index)?;
+    Ok(Segment {
+        first,
+        count: offsets.len(),
+        bytes: position as u64,
+    })
+}
+
+#[cfg(test)]
+fn test_config(segment_size: u64, max_segments: usize) -> EventStoreConfig {
+    EventStoreConfig {
+        dir: std::env::temp_dir().join(format!("event-store-{}", uuid::Uuid::new_v4())),
+        fsync: FsyncPolicy::Always,
+        segment_size,
+        max_segments,
+    }
+}
+
+#[test]
+fn event_store_reopen_test() {
+    let config = test_config(DEFAULT_SEGMENT_SIZE, DEFAULT_MAX_SEGMENTS);
+    let mut store = SegmentStore::<String>::open(config.clone()).unwrap();
+    for i in 0..5 {
+        store.append(i, &format!("event {}", i)).unwrap();
+    }
+    assert!(store.append(7, &"gap".to_string()).is_err());
+    drop(store);
+    let mut store = SegmentStore::<String>::open(config.clone()).unwrap();
+    assert_eq!(store.next_number(), 5);
+    store.append(5, &"event 5".to_string()).unwrap();
+    assert_eq!(
+        store.read_range(3, 10).unwrap(),
+        vec![
+            (3, "event 3".to_string()),
+            (4, "event 4".to_string()),
+            (5, "event 5".to_string())
+        ]
+    );
+    fs::remove_dir_all(config.dir).unwrap();
+}
+
+#[test]
+fn event_store_retention_test() {
+    let config = test_config(64, 3);
+    let mut store = SegmentStore::<String>::open(config.clone()).unwrap();
+    for i in 0..40 {
+        store.append(i, &format!("event {}", i)).unwrap();
+    }
+    assert_eq!(store.segments.len(), 3);
+    let first = store.segments[0].first;
+    assert!(first > 0);
+    let events = store.read_range(0, 40).unwrap();
+    assert_eq!(events.first().unwrap().0, first);
+    assert_eq!(
+        events.iter().map(|x| x.0).collect::<Vec<_>>(),
+        (first..40).collect::<Vec<_>>()
+    );
+    drop(store);
+    let store = SegmentStore::<String>::open(config.clone()).unwrap();
+    assert_eq!(store.segments[0].first, first);
+    assert_eq!(store.next_number(), 40);
+    fs::remove_dir_all(config.dir).unwrap();
+}
+
+#[test]
+fn event_store_recovers_torn_write_test() {
+    let config = test_config(DEFAULT_SEGMENT_SIZE, DEFAULT_MAX_SEGMENTS);
+    let mut store = SegmentStore::<String>::open(config.clone()).unwrap();
+    for i in 0..3 {
+        store.append(i, &format!("event {}", i)).unwrap();
+    }
+    drop(store);
+    let log_path = segment_path(&config.dir, 0, LOG_EXTENSION);
+    let mut log = OpenOptions::new().append(true).open(&log_path).unwrap();
+    log.write_all(b"{\"n\":3,\"e\":\"tor").unwrap();
+    drop(log);
+    let mut store = SegmentStore::<String>::open(config.clone()).unwrap();
+    assert_eq!(store.next_number(), 3);
+    store.append(3, &"event 3".to_string()).unwrap();
+    assert_eq!(
+        store.read_range(2, 4).unwrap(),
+        vec![(2, "event 2".to_string()), (3, "event 3".to_string())]
+    );
+    fs::remove_dir_all(config.dir).unwrap();
+}
+
+#[test]
+fn event_store_corrupted_line_test() {
+    let config = test_config(DEFAULT_SEGMENT_SIZE, DEFAULT_MAX_SEGMENTS);
+    let mut store = SegmentStore::<String>::open(config.clone()).unwrap();
+    store.append(0, &"event 0".to_string()).unwrap();
+    drop(store);
+    let log_path = segment_path(&config.dir, 0, LOG_EXTENSION);
+    let mut log = OpenOptions::new().append(true).open(&log_path).unwrap();
+    log.write_all(b"{\"n\":1,\"e\":\"corrupted\n{\"n\":2,\"e\":\"event 2\"}\n")
+        .unwrap();
+    drop(log);
+    let length = fs::metadata(&log_path).unwrap().len();
+    let error = SegmentStore::<String>::open(config.clone()).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains("Event 1"), "{}", error);
+    assert_eq!(fs::metadata(&log_path).unwrap().len(), length);
+    fs::remove_dir_all(config.dir).unwrap();
+}
+
+#[test]
+fn fsync_policy_parse_test() {
+    assert_eq!("always".parse(), Ok(FsyncPolicy::Always));
+    assert_eq!("never".parse(), Ok(FsyncPolicy::Never));
+    assert_eq!("10".parse(), Ok(FsyncPolicy::Every(10)));
+    assert!("0".parse::<FsyncPolicy>().is_err());
+    assert!("sometimes".parse::<FsyncPolicy>().is_err());
+}
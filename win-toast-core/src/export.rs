@@ -0,0 +1,509 @@
+//! Registration files for deployment without running this tool on every machine.
+//!
+//! [`plan_export_register`] plans the keys `register` creates on a machine without registrations
+//! and [`plan_export_unregister`] the keys `un-register` removes. [`export`] writes such plans as
+//! a `.reg` file, a PowerShell script or a JSON description.
+
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+use serde_json::json;
+
+#[cfg(feature = "manifest")]
+use crate::manifest::Manifest;
+use crate::registration::{
+    app_id_key, launch_command_key, normalize_clsid, plan_register, validate_app_id,
+    AppRegistration, Change, Plan, Scope, CREATED_BY, CREATED_BY_VALUE,
+};
+use crate::registry::{MemoryRegistry, RegValue};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Registry editor file, imported with `reg import`.
+    Reg,
+    PowerShell,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Reg => "reg",
+            ExportFormat::PowerShell => "ps1",
+            ExportFormat::Json => "json",
+        }
+    }
+
+    /// Bytes of the exported file: UTF-16 for `.reg` files as written by the registry editor,
+    /// UTF-8 with BOM for scripts so that Windows PowerShell reads non ASCII names.
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        match self {
+            ExportFormat::Reg => {
+                let text = text.replace('\n', "\r\n");
+                let utf16 = std::iter::once(0xFEFF).chain(text.encode_utf16());
+                utf16.flat_map(|unit: u16| unit.to_le_bytes()).collect()
+            }
+            ExportFormat::PowerShell => {
+                let mut bytes = vec![0xEF, 0xBB, 0xBF];
+                bytes.extend(text.as_bytes());
+                bytes
+            }
+            ExportFormat::Json => text.as_bytes().to_vec(),
+        }
+    }
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFormat::Reg => write!(f, "reg"),
+            ExportFormat::PowerShell => write!(f, "powershell"),
+            ExportFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reg" => Ok(ExportFormat::Reg),
+            "powershell" | "ps1" => Ok(ExportFormat::PowerShell),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(format!(
+                "[{}] is not a valid export format, expected reg, powershell or json",
+                s
+            )),
+        }
+    }
+}
+
+/// Changes which register the application id in `scope` of a machine where it is not registered.
+/// Icons are not converted, the icon has to be a PNG file deployed at its path.
+///
+/// Activator keys are not marked as created by this tool, the target machine may have the class
+/// registered already and `un-register` would remove it otherwise. The `LocalServer32` launch
+/// command is overwritten.
+pub fn plan_export_register(registration: &AppRegistration, scope: Scope) -> Result<Plan, String> {
+    if let Some(icon_path) = &registration.icon_path {
+        let is_png = Path::new(icon_path)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+        if !is_png {
+            return Err(format!(
+                "Icon [{}] of {} is not converted by export, convert it to a png file \
+                 which is deployed at the same path on target machines.",
+                icon_path, registration.app_id
+            ));
+        }
+    }
+    let mut plan = plan_register(&MemoryRegistry::new(&scope.root_name()), registration)?;
+    let app_key = app_id_key(&registration.app_id);
+    plan.changes.retain(|change| {
+        !matches!(change, Change::SetValue { key, name, .. }
+            if name == CREATED_BY_VALUE && *key != app_key)
+    });
+    Ok(plan)
+}
+
+/// Changes which register the manifest applications of `scope`, or of both scopes when it is None.
+#[cfg(feature = "manifest")]
+pub fn plan_export_manifest(
+    manifest: &Manifest,
+    scope: Option<Scope>,
+) -> Result<Vec<Plan>, String> {
+    let scopes = match scope {
+        Some(scope) => vec![scope],
+        None => vec![Scope::User, Scope::Machine],
+    };
+    let mut plans = vec![];
+    for scope in scopes {
+        for registration in manifest.registrations(scope) {
+            plans.push(plan_export_register(&registration, scope)?);
+        }
+    }
+    Ok(plans)
+}
+
+/// Changes which remove the application id registration and the `LocalServer32` launch command
+/// key of the `custom_activator`, whether they exist or not. The class key of the activator is
+/// kept, it may be registered by another product. See [`export`] for the keys each format removes.
+pub fn plan_export_unregister(
+    app_id: &str,
+    custom_activator: Option<&str>,
+    scope: Scope,
+) -> Result<Plan, String> {
+    validate_app_id(app_id)?;
+    let mut plan = Plan::new(&scope.root_name());
+    let mut keys = vec![app_id_key(app_id)];
+    if let Some(clsid) = custom_activator {
+        let clsid = normalize_clsid(clsid)?;
+        keys.push(launch_command_key(&clsid));
+    }
+    for key in keys {
+        plan.changes.push(Change::DeleteKey {
+            key,
+            values: vec![],
+        });
+    }
+    Ok(plan)
+}
+
+/// Registry changes of the plans in `format`, file changes are not exported. PowerShell scripts
+/// remove only keys marked as created by this tool, `.reg` and JSON files cannot check it.
+pub fn export(plans: &[Plan], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Reg => export_reg(plans),
+        ExportFormat::PowerShell => export_powershell(plans),
+        ExportFormat::Json => export_json(plans),
+    }
+}
+
+fn reg_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn reg_value(value: &RegValue) -> String {
+    match value {
+        RegValue::String(value) => reg_string(value),
+        RegValue::Dword(_) => value.to_string(),
+    }
+}
+
+fn reg_name(name: &str) -> String {
+    match name {
+        "" => "@".to_string(),
+        name => reg_string(name),
+    }
+}
+
+fn export_reg(plans: &[Plan]) -> String {
+    let mut text = String::from("Windows Registry Editor Version 5.00\n");
+    let mut current_key: Option<String> = None;
+    let mut section = |text: &mut String, header: String| {
+        if current_key.as_ref() != Some(&header) {
+            text.push_str(&format!("\n{}\n", header));
+            current_key = Some(header);
+        }
+    };
+    for plan in plans {
+        for change in plan.changes.iter() {
+            match change {
+                Change::CreateKey(key) => section(&mut text, format!(r"[{}\{}]", plan.root, key)),
+                Change::SetValue { key, name, new, .. } => {
+                    section(&mut text, format!(r"[{}\{}]", plan.root, key));
+                    text.push_str(&format!("{}={}\n", reg_name(name), reg_value(new)));
+                }
+                Change::DeleteValue { key, name, .. } => {
+                    section(&mut text, format!(r"[{}\{}]", plan.root, key));
+                    text.push_str(&format!("{}=-\n", reg_name(name)));
+                }
+                Change::DeleteKey { key, .. } => {
+                    section(&mut text, format!(r"[-{}\{}]", plan.root, key))
+                }
+                Change::CreateFile(_) | Change::DeleteFile(_) => {}
+            }
+        }
+    }
+    text
+}
+
+/// Single quoted PowerShell string.
+fn ps_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn ps_path(root: &str, key: &str) -> String {
+    ps_string(&format!(r"Registry::{}\{}", root, key))
+}
+
+fn export_powershell(plans: &[Plan]) -> String {
+    let mut text = String::from("$ErrorActionPreference = 'Stop'\n");
+    for plan in plans {
+        for change in plan.changes.iter() {
+            let line = match change {
+                Change::CreateKey(key) => {
+                    let path = ps_path(&plan.root, key);
+                    format!(
+                        "if (-not (Test-Path -LiteralPath {0})) {{ New-Item -Path {0} -Force | Out-Null }}",
+                        path
+                    )
+                }
+                Change::SetValue { key, name, new, .. } if name.is_empty() => {
+                    let value = match new {
+                        RegValue::String(value) => ps_string(value),
+                        RegValue::Dword(value) => value.to_string(),
+                    };
+                    format!(
+                        "Set-Item -LiteralPath {} -Value {}",
+                        ps_path(&plan.root, key),
+                        value
+                    )
+                }
+                Change::SetValue { key, name, new, .. } => {
+                    let (kind, value) = match new {
+                        RegValue::String(value) => ("String", ps_string(value)),
+                        RegValue::Dword(value) => ("DWord", value.to_string()),
+                    };
+                    format!(
+                        "New-ItemProperty -LiteralPath {} -Name {} -PropertyType {} -Value {} -Force | Out-Null",
+                        ps_path(&plan.root, key),
+                        ps_string(name),
+                        kind,
+                        value
+                    )
+                }
+                Change::DeleteValue { key, name, .. } => format!(
+                    "Remove-ItemProperty -LiteralPath {} -Name {} -ErrorAction SilentlyContinue",
+                    ps_path(&plan.root, key),
+                    ps_string(name)
+                ),
+                Change::DeleteKey { key, .. } => {
+                    let path = ps_path(&plan.root, key);
+                    format!(
+                        "if ((Get-ItemProperty -LiteralPath {0} -Name {1} -ErrorAction SilentlyContinue).{2} -eq {3}) \
+                         {{ Remove-Item -LiteralPath {0} -Recurse -Force }}",
+                        path,
+                        ps_string(CREATED_BY_VALUE),
+                        CREATED_BY_VALUE,
+                        ps_string(CREATED_BY)
+                    )
+                }
+                Change::CreateFile(_) | Change::DeleteFile(_) => continue,
+            };
+            text.push_str(&line);
+            text.push('\n');
+        }
+    }
+    text
+}
+
+fn export_json(plans: &[Plan]) -> String {
+    let mut changes = vec![];
+    for plan in plans {
+        let full_key = |key: &str| format!(r"{}\{}", plan.root, key);
+        for change in plan.changes.iter() {
+            let change = match change {
+                Change::CreateKey(key) => json!({ "action": "createKey", "key": full_key(key) }),
+                Change::SetValue { key, name, new, .. } => {
+                    let (kind, value) = match new {
+                        RegValue::String(value) => ("string", json!(value)),
+                        RegValue::Dword(value) => ("dword", json!(value)),
+                    };
+                    json!({
+                        "action": "setValue",
+                        "key": full_key(key),
+                        "name": name,
+                        "type": kind,
+                        "value": value,
+                    })
+                }
+                Change::DeleteValue { key, name, .. } => {
+                    json!({ "action": "deleteValue", "key": full_key(key), "name": name })
+                }
+                Change::DeleteKey { key, .. } => {
+                    json!({ "action": "deleteKey", "key": full_key(key) })
+                }
+                Change::CreateFile(_) | Change::DeleteFile(_) => continue,
+            };
+            changes.push(change);
+        }
+    }
+    let mut text = serde_json::to_string_pretty(&json!({ "changes": changes })).unwrap_or_default();
+    text.push('\n');
+    text
+}
+
+#[cfg(all(test, feature = "manifest"))]
+use crate::manifest::parse_manifest;
+
+#[cfg(test)]
+fn export_test_plans() -> (Vec<Plan>, Vec<Plan>) {
+    let registration = AppRegistration {
+        display_name: Some(r#"App "One""#.into()),
+        icon_path: Some(r"C:\Icons\app.png".into()),
+        show_in_settings: Some(false),
+        custom_activator: Some("c3d7a5e8-0f3b-4d6c-9a8e-2b1f0e7d6c5a".into()),
+        launch_command: Some(r#""C:\App\app's.exe" -ToastActivated"#.into()),
+        ..AppRegistration::new("com.app")
+    };
+    let register = plan_export_register(&registration, Scope::Machine).unwrap();
+    let activator = registration.custom_activator.as_deref();
+    let unregister = plan_export_unregister("com.app", activator, Scope::Machine).unwrap();
+    (vec![register], vec![unregister])
+}
+
+#[test]
+fn export_icon_test() {
+    for icon_path in [r"C:\Icons\app.svg", r"C:\Icons\app.ico", "app"] {
+        let registration = AppRegistration {
+            icon_path: Some(icon_path.into()),
+            ..AppRegistration::new("com.app")
+        };
+        let error = plan_export_register(&registration, Scope::User).unwrap_err();
+        assert!(error.contains("is not converted by export"), "{}", error);
+    }
+    let registration = AppRegistration {
+        icon_path: Some(r"C:\Icons\app.PNG".into()),
+        ..AppRegistration::new("com.app")
+    };
+    assert!(plan_export_register(&registration, Scope::User).is_ok());
+}
+
+#[test]
+fn export_reg_test() {
+    let (register, unregister) = export_test_plans();
+    assert_eq!(
+        export(&register, ExportFormat::Reg),
+        concat!(
+            "Windows Registry Editor Version 5.00\n",
+            "\n",
+            "[HKEY_LOCAL_MACHINE\\SOFTWARE\\Classes\\AppUserModelId\\com.app]\n",
+            "\"CreatedBy\"=\"win-toast-notifier\"\n",
+            "\"DisplayName\"=\"App \\\"One\\\"\"\n",
+            "\"IconUri\"=\"C:\\\\Icons\\\\app.png\"\n",
+            "\"ShowInSettings\"=dword:00000000\n",
+            "\"CustomActivator\"=\"{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}\"\n",
+            "\n",
+            "[HKEY_LOCAL_MACHINE\\SOFTWARE\\Classes\\CLSID\\{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}]\n",
+            "\n",
+            "[HKEY_LOCAL_MACHINE\\SOFTWARE\\Classes\\CLSID\\{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}\\LocalServer32]\n",
+            "@=\"\\\"C:\\\\App\\\\app's.exe\\\" -ToastActivated\"\n",
+        )
+    );
+    assert_eq!(
+        export(&unregister, ExportFormat::Reg),
+        concat!(
+            "Windows Registry Editor Version 5.00\n",
+            "\n",
+            "[-HKEY_LOCAL_MACHINE\\SOFTWARE\\Classes\\AppUserModelId\\com.app]\n",
+            "\n",
+            "[-HKEY_LOCAL_MACHINE\\SOFTWARE\\Classes\\CLSID\\{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}\\LocalServer32]\n",
+        )
+    );
+    let bytes = ExportFormat::Reg.encode("a\n");
+    assert_eq!(bytes, vec![0xFF, 0xFE, b'a', 0, b'\r', 0, b'\n', 0]);
+}
+
+#[test]
+fn export_powershell_test() {
+    let (register, unregister) = export_test_plans();
+    let script = export(&register, ExportFormat::PowerShell);
+    let lines: Vec<&str> = script.lines().collect();
+    let app_key = r"'Registry::HKEY_LOCAL_MACHINE\SOFTWARE\Classes\AppUserModelId\com.app'";
+    assert_eq!(lines[0], "$ErrorActionPreference = 'Stop'");
+    assert_eq!(
+        lines[1],
+        format!(
+            "if (-not (Test-Path -LiteralPath {0})) {{ New-Item -Path {0} -Force | Out-Null }}",
+            app_key
+        )
+    );
+    assert_eq!(
+        lines[3],
+        format!(
+            "New-ItemProperty -LiteralPath {} -Name 'DisplayName' -PropertyType String \
+             -Value 'App \"One\"' -Force | Out-Null",
+            app_key
+        )
+    );
+    assert_eq!(
+        lines[5],
+        format!(
+            "New-ItemProperty -LiteralPath {} -Name 'ShowInSettings' -PropertyType DWord \
+             -Value 0 -Force | Out-Null",
+            app_key
+        )
+    );
+    assert_eq!(
+        lines.last().unwrap(),
+        &concat!(
+            r"Set-Item -LiteralPath 'Registry::HKEY_LOCAL_MACHINE\SOFTWARE\Classes\CLSID\",
+            r#"{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}\LocalServer32' -Value '"C:\App\app''s.exe" -ToastActivated'"#
+        )
+    );
+    let script = export(&unregister, ExportFormat::PowerShell);
+    assert_eq!(script.lines().count(), 3);
+    assert_eq!(
+        script.lines().nth(1).unwrap(),
+        format!(
+            "if ((Get-ItemProperty -LiteralPath {0} -Name 'CreatedBy' -ErrorAction SilentlyContinue)\
+             .CreatedBy -eq 'win-toast-notifier') {{ Remove-Item -LiteralPath {0} -Recurse -Force }}",
+            app_key
+        )
+    );
+    assert!(ExportFormat::PowerShell
+        .encode("x")
+        .starts_with(&[0xEF, 0xBB, 0xBF]));
+}
+
+#[test]
+fn export_json_test() {
+    let (register, unregister) = export_test_plans();
+    let json: serde_json::Value =
+        serde_json::from_str(&export(&register, ExportFormat::Json)).unwrap();
+    assert_eq!(
+        json["changes"][0],
+        json!({
+            "action": "createKey",
+            "key": r"HKEY_LOCAL_MACHINE\SOFTWARE\Classes\AppUserModelId\com.app"
+        })
+    );
+    assert_eq!(
+        json["changes"][4],
+        json!({
+            "action": "setValue",
+            "key": r"HKEY_LOCAL_MACHINE\SOFTWARE\Classes\AppUserModelId\com.app",
+            "name": "ShowInSettings",
+            "type": "dword",
+            "value": 0
+        })
+    );
+    let json: serde_json::Value =
+        serde_json::from_str(&export(&unregister, ExportFormat::Json)).unwrap();
+    assert_eq!(json["changes"].as_array().unwrap().len(), 2);
+    assert_eq!(
+        json["changes"][1],
+        json!({
+            "action": "deleteKey",
+            "key": r"HKEY_LOCAL_MACHINE\SOFTWARE\Classes\CLSID\{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}\LocalServer32"
+        })
+    );
+    assert_eq!("ps1".parse(), Ok(ExportFormat::PowerShell));
+    assert!("bat".parse::<ExportFormat>().is_err());
+}
+
+#[cfg(feature = "manifest")]
+#[test]
+fn export_manifest_test() {
+    let text = concat!(
+        "[[app]]\nid = \"com.machine\"\nscope = \"machine\"\n",
+        "[[app]]\nid = \"com.user\"\n",
+    );
+    let manifest = parse_manifest(text, std::path::Path::new("/")).unwrap();
+    let roots = |scope: Option<Scope>| -> Vec<String> {
+        plan_export_manifest(&manifest, scope)
+            .unwrap()
+            .into_iter()
+            .map(|plan| plan.root)
+            .collect()
+    };
+    assert_eq!(
+        roots(None),
+        vec![
+            r"HKEY_CURRENT_USER\Software\Classes",
+            r"HKEY_LOCAL_MACHINE\SOFTWARE\Classes"
+        ]
+    );
+    assert_eq!(
+        roots(Some(Scope::Machine)),
+        vec![r"HKEY_LOCAL_MACHINE\SOFTWARE\Classes"]
+    );
+    let text = export(
+        &plan_export_manifest(&manifest, None).unwrap(),
+        ExportFormat::Reg,
+    );
+    assert!(text.contains(r"[HKEY_CURRENT_USER\Software\Classes\AppUserModelId\com.user]"));
+}
@@ -0,0 +1,167 @@
+//! Conversion of application icons to PNG for registration.
+//!
+//! `IconUri` of an application id has to point to a PNG file. ICO, JPEG and SVG icons are
+//! converted to a square [`ICON_SIZE`] PNG, which is stored at [`generated_icon_path`].
+
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use image::imageops::{self, FilterType};
+use image::{ImageFormat, RgbaImage};
+use resvg::{tiny_skia, usvg};
+
+/// Width and height of converted icons in pixels.
+pub const ICON_SIZE: u32 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IconFormat {
+    Png,
+    Ico,
+    Jpeg,
+    Svg,
+}
+
+/// Format of the icon by its file extension.
+pub fn icon_format(path: &Path) -> Result<IconFormat, String> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase());
+    match extension.as_deref() {
+        Some("png") => Ok(IconFormat::Png),
+        Some("ico") => Ok(IconFormat::Ico),
+        Some("jpg") | Some("jpeg") => Ok(IconFormat::Jpeg),
+        Some("svg") => Ok(IconFormat::Svg),
+        _ => Err("Icon path should have png, ico, jpg or svg extension.".into()),
+    }
+}
+
+/// Path of the PNG converted from the icon of the application id.
+pub fn generated_icon_path(data_dir: &Path, app_id: &str) -> PathBuf {
+    data_dir
+        .join("win-toast-notifier")
+        .join(app_id)
+        .join("icon.png")
+}
+
+/// Converts icon to a `size` x `size` PNG. The image is scaled to fit and centered on a
+/// transparent background, ICO files are converted from their largest image.
+pub fn convert_to_png(data: &[u8], format: IconFormat, size: u32) -> Result<Vec<u8>, String> {
+    let image = match format {
+        IconFormat::Svg => render_svg(data, size)?,
+        IconFormat::Png | IconFormat::Ico | IconFormat::Jpeg => {
+            let format = match format {
+                IconFormat::Png => ImageFormat::Png,
+                IconFormat::Ico => ImageFormat::Ico,
+                _ => ImageFormat::Jpeg,
+            };
+            let image = image::load_from_memory_with_format(data, format)
+                .map_err(|e| format!("Could not decode icon: {}", e))?;
+            let image = image.resize(size, size, FilterType::Lanczos3).to_rgba8();
+            let mut canvas = RgbaImage::new(size, size);
+            let x = (size - image.width()) / 2;
+            let y = (size - image.height()) / 2;
+            imageops::overlay(&mut canvas, &image, x as i64, y as i64);
+            canvas
+        }
+    };
+    let mut png = vec![];
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| format!("Could not encode icon: {}", e))?;
+    Ok(png)
+}
+
+fn render_svg(data: &[u8], size: u32) -> Result<RgbaImage, String> {
+    let tree = usvg::Tree::from_data(data, &usvg::Options::default())
+        .map_err(|e| format!("Could not parse svg icon: {}", e))?;
+    let mut pixmap = tiny_skia::Pixmap::new(size, size).ok_or("Invalid icon size".to_string())?;
+    let svg_size = tree.size();
+    let scale = (size as f32 / svg_size.width()).min(size as f32 / svg_size.height());
+    let x = (size as f32 - svg_size.width() * scale) / 2.0;
+    let y = (size as f32 - svg_size.height() * scale) / 2.0;
+    let transform = tiny_skia::Transform::from_row(scale, 0.0, 0.0, scale, x, y);
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    RgbaImage::from_raw(size, size, pixels).ok_or("Could not render svg icon".to_string())
+}
+
+#[cfg(test)]
+fn encode(image: &RgbaImage, format: ImageFormat) -> Vec<u8> {
+    let mut data = vec![];
+    match format {
+        ImageFormat::Jpeg => image::DynamicImage::ImageRgba8(image.clone())
+            .to_rgb8()
+            .write_to(&mut Cursor::new(&mut data), format),
+        _ => image.write_to(&mut Cursor::new(&mut data), format),
+    }
+    .unwrap();
+    data
+}
+
+#[test]
+fn icon_format_test() {
+    assert_eq!(icon_format(Path::new("a.PNG")), Ok(IconFormat::Png));
+    assert_eq!(icon_format(Path::new("a.jpeg")), Ok(IconFormat::Jpeg));
+    assert_eq!(icon_format(Path::new(r"C:\a.svg")), Ok(IconFormat::Svg));
+    assert!(icon_format(Path::new("a.gif")).is_err());
+    assert!(icon_format(Path::new("icon")).is_err());
+}
+
+#[test]
+fn convert_raster_test() {
+    let red = RgbaImage::from_pixel(32, 16, image::Rgba([255, 0, 0, 255]));
+    for format in [IconFormat::Ico, IconFormat::Jpeg, IconFormat::Png] {
+        let data = match format {
+            IconFormat::Ico => encode(&red, ImageFormat::Ico),
+            IconFormat::Jpeg => encode(&red, ImageFormat::Jpeg),
+            _ => encode(&red, ImageFormat::Png),
+        };
+        let png = convert_to_png(&data, format, 64).unwrap();
+        let image = image::load_from_memory_with_format(&png, ImageFormat::Png)
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(image.dimensions(), (64, 64));
+        // 32x16 image is scaled to 64x32 and centered vertically.
+        assert_eq!(image.get_pixel(32, 0)[3], 0, "{:?}", format);
+        let center = image.get_pixel(32, 32);
+        assert!(
+            center[0] > 240 && center[1] < 16 && center[3] == 255,
+            "{:?}",
+            format
+        );
+    }
+}
+
+#[test]
+fn convert_svg_test() {
+    let svg = br#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="20">
+        <rect width="10" height="20" fill="blue"/></svg>"#;
+    let png = convert_to_png(svg, IconFormat::Svg, 40).unwrap();
+    let image = image::load_from_memory_with_format(&png, ImageFormat::Png)
+        .unwrap()
+        .to_rgba8();
+    assert_eq!(image.dimensions(), (40, 40));
+    assert_eq!(image.get_pixel(20, 20), &image::Rgba([0, 0, 255, 255]));
+    assert_eq!(image.get_pixel(2, 20)[3], 0);
+    assert!(convert_to_png(b"<svg", IconFormat::Svg, 40).is_err());
+    assert!(convert_to_png(b"not an image", IconFormat::Ico, 40).is_err());
+}
+
+#[test]
+fn generated_icon_path_test() {
+    assert_eq!(
+        generated_icon_path(Path::new("data"), "App"),
+        Path::new("data")
+            .join("win-toast-notifier")
+            .join("App")
+            .join("icon.png")
+    );
+}
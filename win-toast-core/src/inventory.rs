@@ -0,0 +1,224 @@
+//! Listing of registered application ids and Start menu shortcuts.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::lnk;
+use crate::registration::{Scope, CREATED_BY, CREATED_BY_VALUE, SHORTCUT_DESCRIPTION};
+use crate::registry::{RegValue, Registry};
+
+/// Application id registered under `AppUserModelId`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisteredApp {
+    pub application_id: String,
+    pub scope: Scope,
+    pub display_name: Option<String>,
+    pub icon_uri: Option<String>,
+    pub icon_exists: bool,
+    /// Registration has the `CreatedBy` marker of this tool.
+    pub created_by_tool: bool,
+}
+
+/// Shortcut in the Start menu programs directory.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Shortcut {
+    pub path: String,
+    /// File name without extension, the name shown in the Start menu.
+    pub display_name: String,
+    pub target: Option<String>,
+    /// Icon location of the shortcut, its target when the location is not set.
+    pub icon: Option<String>,
+    pub icon_exists: bool,
+    /// `System.AppUserModel.ID` of the shortcut.
+    pub app_user_model_id: Option<String>,
+    /// Shortcut has the description written by this tool.
+    pub created_by_tool: bool,
+}
+
+fn string_value(values: &[(String, RegValue)], name: &str) -> Option<String> {
+    values.iter().find_map(|(value_name, value)| match value {
+        RegValue::String(value) if value_name.eq_ignore_ascii_case(name) => Some(value.clone()),
+        _ => None,
+    })
+}
+
+/// Icon location without the `,index` suffix.
+fn icon_file(icon: &str) -> &str {
+    match icon.rsplit_once(',') {
+        Some((file, index)) if index.trim().parse::<i32>().is_ok() => file,
+        _ => icon,
+    }
+}
+
+/// Application ids registered below `key` of the registry of `scope`, sorted by id.
+pub fn list_registrations(
+    registry: &dyn Registry,
+    key: &str,
+    scope: Scope,
+) -> Result<Vec<RegisteredApp>, String> {
+    let mut apps = vec![];
+    for app_id in registry.subkeys(key).map_err(|e| e.to_string())? {
+        let values = registry
+            .values(&format!(r"{}\{}", key, app_id))
+            .map_err(|e| e.to_string())?;
+        let icon_uri = string_value(&values, "IconUri");
+        let created_by = string_value(&values, CREATED_BY_VALUE);
+        apps.push(RegisteredApp {
+            application_id: app_id,
+            scope,
+            display_name: string_value(&values, "DisplayName"),
+            icon_exists: icon_uri
+                .as_ref()
+                .is_some_and(|icon| Path::new(icon).is_file()),
+            icon_uri,
+            created_by_tool: created_by.as_deref() == Some(CREATED_BY),
+        });
+    }
+    apps.sort_by(|a, b| a.application_id.cmp(&b.application_id));
+    Ok(apps)
+}
+
+/// `.lnk` files directly in `dir`, sorted by path. Files which are not shell links are skipped.
+pub fn list_shortcuts(dir: &Path) -> Result<Vec<Shortcut>, String> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.to_string()),
+    };
+    let mut shortcuts = vec![];
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        let is_link = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("lnk"));
+        if !is_link || !path.is_file() {
+            continue;
+        }
+        let Ok(info) = std::fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| lnk::read_shell_link(&data))
+        else {
+            continue;
+        };
+        let target = info.target();
+        let icon = info.icon_location.clone().or(target.clone());
+        shortcuts.push(Shortcut {
+            display_name: path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            path: path.display().to_string(),
+            target,
+            icon_exists: icon
+                .as_ref()
+                .is_some_and(|icon| Path::new(icon_file(icon)).is_file()),
+            icon,
+            app_user_model_id: info.app_user_model_id.clone(),
+            created_by_tool: info.name.as_deref() == Some(SHORTCUT_DESCRIPTION),
+        });
+    }
+    shortcuts.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(shortcuts)
+}
+
+#[cfg(test)]
+use crate::registration::{plan_register, AppRegistration, APP_USER_MODEL_ID};
+#[cfg(test)]
+use crate::registry::MemoryRegistry;
+
+#[test]
+fn list_registrations_test() {
+    let mut registry = MemoryRegistry::new("HKEY_CLASSES_ROOT");
+    let icon = std::env::temp_dir().join(format!("inventory-icon-{}.png", std::process::id()));
+    std::fs::write(&icon, b"png").unwrap();
+    let registration = AppRegistration {
+        display_name: Some("My app".into()),
+        icon_path: Some(icon.display().to_string()),
+        ..AppRegistration::new("com.app")
+    };
+    plan_register(&registry, &registration)
+        .unwrap()
+        .apply(&mut registry)
+        .unwrap();
+    let other = format!(r"{}\Other", APP_USER_MODEL_ID);
+    registry.create_key(&other).unwrap();
+    let missing_icon = RegValue::String(r"C:\missing.png".into());
+    registry
+        .set_value(&other, "IconUri", &missing_icon)
+        .unwrap();
+    let apps = list_registrations(&registry, APP_USER_MODEL_ID, Scope::User).unwrap();
+    std::fs::remove_file(&icon).unwrap();
+    assert_eq!(
+        apps,
+        vec![
+            RegisteredApp {
+                application_id: "Other".into(),
+                scope: Scope::User,
+                display_name: None,
+                icon_uri: Some(r"C:\missing.png".into()),
+                icon_exists: false,
+                created_by_tool: false,
+            },
+            RegisteredApp {
+                application_id: "com.app".into(),
+                scope: Scope::User,
+                display_name: Some("My app".into()),
+                icon_uri: Some(icon.display().to_string()),
+                icon_exists: true,
+                created_by_tool: true,
+            },
+        ]
+    );
+    let json = serde_json::to_value(&apps[1]).unwrap();
+    assert_eq!(json["applicationId"], "com.app");
+    assert_eq!(json["createdByTool"], true);
+    assert_eq!(json["scope"], "user");
+}
+
+#[test]
+fn list_shortcuts_test() {
+    let dir = std::env::temp_dir().join(format!("inventory-links-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let link = |name: Option<&str>, working_dir: &str| {
+        let mut data = vec![0u8; 0x4C];
+        data[0] = 0x4C;
+        let flags: u32 = 0x8 | 0x10 | 0x80 | if name.is_some() { 0x4 } else { 0 };
+        data[0x14..0x18].copy_from_slice(&flags.to_le_bytes());
+        for value in name.into_iter().chain(["./app.exe", working_dir]) {
+            let chars: Vec<u16> = value.encode_utf16().collect();
+            data.extend((chars.len() as u16).to_le_bytes());
+            data.extend(chars.iter().flat_map(|c| c.to_le_bytes()));
+        }
+        data
+    };
+    let app_dir = dir.display().to_string();
+    std::fs::write(
+        dir.join("Ours.lnk"),
+        link(Some(SHORTCUT_DESCRIPTION), &app_dir),
+    )
+    .unwrap();
+    std::fs::write(dir.join("Theirs.lnk"), link(None, r"C:\Other")).unwrap();
+    std::fs::write(dir.join("Broken.lnk"), b"broken").unwrap();
+    std::fs::write(dir.join("readme.txt"), b"text").unwrap();
+    let shortcuts = list_shortcuts(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    let summary: Vec<(&str, bool, bool)> = shortcuts
+        .iter()
+        .map(|s| (s.display_name.as_str(), s.created_by_tool, s.icon_exists))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![("Ours", true, false), ("Theirs", false, false)]
+    );
+    assert_eq!(shortcuts[1].target.as_deref(), Some(r"C:\Other\app.exe"));
+    assert!(list_shortcuts(&dir).unwrap().is_empty());
+}
+
+#[test]
+fn icon_file_test() {
+    assert_eq!(icon_file(r"C:\app.exe,0"), r"C:\app.exe");
+    assert_eq!(icon_file(r"C:\a,b.ico"), r"C:\a,b.ico");
+}
@@ -0,0 +1,47 @@
+//! Platform independent parts of win-toast-notifier.
+//!
+//! * [`event_log`] - pub/sub log which numbers events and replays them to late subscribers
+//! * [`event_store`] - segment files persisting the event log between restarts
+//! * [`ring_buffer`] - buffer which keeps the latest events of the log by count, size and age
+//! * [`artifacts`] - artifacts created by this tool which `un-register --all` removes
+//! * [`doctor`] - checks explaining why toasts of an application id are not shown
+//! * [`export`] - registration files for deployment as `.reg`, PowerShell or JSON
+//! * [`icon`] - conversion of ICO, JPEG and SVG application icons to PNG, `icon` feature
+//! * [`inventory`] - listing of registered application ids and Start menu shortcuts
+//! * [`lnk`] - reading and writing of shell link files
+//! * [`manifest`] - manifest describing several application ids to register, `manifest` feature
+//! * [`model`] - notification statuses published by the notifier
+//! * [`registry`] - registry access with an in-memory implementation
+//! * [`registration`] - planning of application id registrations as a diff of registry changes
+//! * [`toast_xml`] - toast notification XML building
+//!
+//! ```
+//! use win_toast_core::event_log::{event_log, LogItem};
+//!
+//! # tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+//! let sender = event_log::<String>(100);
+//! sender.send("first".to_string()).await.unwrap();
+//! let mut subscriber = sender.subscribe_from(0).await;
+//! match subscriber.recv().await {
+//!     Some(LogItem::Event(number, event)) => assert_eq!((number, event.as_str()), (0, "first")),
+//!     item => panic!("unexpected {:?}", item),
+//! }
+//! # });
+//! ```
+
+pub mod artifacts;
+pub mod doctor;
+pub mod event_log;
+pub mod event_store;
+pub mod export;
+#[cfg(feature = "icon")]
+pub mod icon;
+pub mod inventory;
+pub mod lnk;
+#[cfg(feature = "manifest")]
+pub mod manifest;
+pub mod model;
+pub mod registration;
+pub mod registry;
+pub mod ring_buffer;
+pub mod toast_xml;
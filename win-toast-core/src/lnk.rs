@@ -0,0 +1,719 @@
+//! Reading and writing of Windows shell link (`.lnk`) files, see [MS-SHLLINK].
+//!
+//! Written links carry `System.AppUserModel.ID` and `System.AppUserModel.ToastActivatorCLSID`
+//! in a property store block ([MS-PROPSTORE]), which Windows uses to attribute toasts.
+//!
+//! [MS-SHLLINK]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-shllink
+//! [MS-PROPSTORE]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-propstore
+
+use crate::registration::{normalize_clsid, SHORTCUT_DESCRIPTION};
+
+/// Size of the shell link header.
+const HEADER_SIZE: usize = 0x4C;
+
+/// `00021401-0000-0000-C000-000000000046` as stored in the header.
+const LINK_CLSID: [u8; 16] = [
+    0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46,
+];
+const FILE_ATTRIBUTE_NORMAL: u32 = 0x80;
+const SW_SHOWNORMAL: u32 = 1;
+
+const HAS_LINK_TARGET_ID_LIST: u32 = 0x1;
+const HAS_LINK_INFO: u32 = 0x2;
+const HAS_NAME: u32 = 0x4;
+const HAS_RELATIVE_PATH: u32 = 0x8;
+const HAS_WORKING_DIR: u32 = 0x10;
+const HAS_ARGUMENTS: u32 = 0x20;
+const HAS_ICON_LOCATION: u32 = 0x40;
+const IS_UNICODE: u32 = 0x80;
+
+/// LinkInfo flag of links with a local base path.
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x1;
+/// LinkInfo header size with the offsets of unicode paths.
+const LINK_INFO_HEADER_SIZE: u32 = 0x24;
+const DRIVE_FIXED: u32 = 3;
+
+const PROPERTY_STORE_SIGNATURE: u32 = 0xA000_0009;
+/// `1SPS`
+const PROPERTY_STORAGE_VERSION: u32 = 0x5350_5331;
+/// Format id of the `System.AppUserModel` properties.
+const APP_USER_MODEL_FORMAT_ID: &str = "{9F4C2855-9F79-4B39-A8D0-E1D42DE1D5F3}";
+/// Property id of `System.AppUserModel.ID`.
+const APP_USER_MODEL_ID_PID: u32 = 5;
+/// Property id of `System.AppUserModel.ToastActivatorCLSID`.
+const TOAST_ACTIVATOR_CLSID_PID: u32 = 26;
+const VT_LPWSTR: u16 = 0x1F;
+const VT_CLSID: u16 = 0x48;
+
+/// Strings and application properties of a shell link, the target ID list and other extra data
+/// blocks are skipped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShellLinkInfo {
+    /// Local path of the target from the link info.
+    pub local_base_path: Option<String>,
+    /// Description of the link.
+    pub name: Option<String>,
+    pub relative_path: Option<String>,
+    pub working_dir: Option<String>,
+    pub arguments: Option<String>,
+    pub icon_location: Option<String>,
+    /// `System.AppUserModel.ID` property.
+    pub app_user_model_id: Option<String>,
+    /// `System.AppUserModel.ToastActivatorCLSID` property in the `{XXXXXXXX-...}` form.
+    pub toast_activator_clsid: Option<String>,
+}
+
+impl ShellLinkInfo {
+    /// Target path, the local base path or the relative path resolved against the working dir.
+    pub fn target(&self) -> Option<String> {
+        if let Some(path) = &self.local_base_path {
+            return Some(path.clone());
+        }
+        let relative = self.relative_path.as_ref()?;
+        let relative = relative
+            .strip_prefix("./")
+            .or(relative.strip_prefix(".\\"))
+            .unwrap_or(relative);
+        match &self.working_dir {
+            Some(dir) => Some(format!("{}\\{}", dir.trim_end_matches('\\'), relative)),
+            None => Some(relative.to_string()),
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.position + len;
+        let bytes = self
+            .data
+            .get(self.position..end)
+            .ok_or("Shell link is truncated".to_string())?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    /// Structure which starts with its size, the size is included in the returned bytes.
+    /// Sizes below 4 mark the end of a list and return no bytes.
+    fn sized(&mut self) -> Result<&'a [u8], String> {
+        let start = self.position;
+        let size = self.u32()? as usize;
+        if size < 4 {
+            return Ok(&[]);
+        }
+        self.position = start;
+        self.bytes(size)
+    }
+
+    /// StringData structure: count of characters followed by UTF-16 or single byte characters.
+    fn string_data(&mut self, unicode: bool) -> Result<String, String> {
+        let count = self.u16()? as usize;
+        match unicode {
+            true => Ok(utf16(self.bytes(count * 2)?)),
+            false => Ok(latin1(self.bytes(count)?)),
+        }
+    }
+}
+
+fn utf16(bytes: &[u8]) -> String {
+    let chars: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&chars)
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
+
+/// Null terminated string starting at `offset`.
+fn terminated(data: &[u8], offset: usize, unicode: bool) -> Option<String> {
+    let data = data.get(offset..)?;
+    match unicode {
+        true => {
+            let len = data.chunks_exact(2).position(|c| c == [0, 0])?;
+            Some(utf16(&data[..len * 2]))
+        }
+        false => {
+            let len = data.iter().position(|b| *b == 0)?;
+            Some(latin1(&data[..len]))
+        }
+    }
+}
+
+/// Local base path and common path suffix of the LinkInfo structure.
+fn local_base_path(link_info: &[u8]) -> Option<String> {
+    let field = |offset: usize| {
+        link_info
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    };
+    let header_size = field(4)?;
+    if field(8)? as u32 & VOLUME_ID_AND_LOCAL_BASE_PATH == 0 {
+        return None;
+    }
+    let (base, suffix) = match header_size >= 0x24 {
+        true => (
+            terminated(link_info, field(0x1C)?, true)?,
+            terminated(link_info, field(0x20)?, true)?,
+        ),
+        false => (
+            terminated(link_info, field(0x10)?, false)?,
+            terminated(link_info, field(0x18)?, false)?,
+        ),
+    };
+    Some(base + &suffix)
+}
+
+/// Reads strings of a shell link.
+pub fn read_shell_link(data: &[u8]) -> Result<ShellLinkInfo, String> {
+    let mut reader = Reader { data, position: 0 };
+    if reader.u32()? as usize != HEADER_SIZE {
+        return Err("Not a shell link".into());
+    }
+    reader.bytes(16)?;
+    let flags = reader.u32()?;
+    reader.position = HEADER_SIZE;
+    if flags & HAS_LINK_TARGET_ID_LIST != 0 {
+        let size = reader.u16()? as usize;
+        reader.bytes(size)?;
+    }
+    let mut info = ShellLinkInfo::default();
+    if flags & HAS_LINK_INFO != 0 {
+        info.local_base_path = local_base_path(reader.sized()?);
+    }
+    let unicode = flags & IS_UNICODE != 0;
+    let strings = [
+        (HAS_NAME, &mut info.name),
+        (HAS_RELATIVE_PATH, &mut info.relative_path),
+        (HAS_WORKING_DIR, &mut info.working_dir),
+        (HAS_ARGUMENTS, &mut info.arguments),
+        (HAS_ICON_LOCATION, &mut info.icon_location),
+    ];
+    for (flag, value) in strings {
+        if flags & flag != 0 {
+            *value = Some(reader.string_data(unicode)?);
+        }
+    }
+    while !reader.is_at_end() {
+        let block = reader.sized()?;
+        if block.len() < 8 {
+            break;
+        }
+        let signature = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+        if signature == PROPERTY_STORE_SIGNATURE {
+            read_property_store(&block[8..], &mut info)?;
+        }
+    }
+    Ok(info)
+}
+
+/// Reads `System.AppUserModel` properties of serialized property storages.
+fn read_property_store(data: &[u8], info: &mut ShellLinkInfo) -> Result<(), String> {
+    let mut storages = Reader { data, position: 0 };
+    let app_user_model = guid_bytes(APP_USER_MODEL_FORMAT_ID)?;
+    while !storages.is_at_end() {
+        let storage = storages.sized()?;
+        if storage.is_empty() {
+            break;
+        }
+        let mut reader = Reader {
+            data: storage,
+            position: 4,
+        };
+        if reader.u32()? != PROPERTY_STORAGE_VERSION {
+            return Err("Invalid property storage".into());
+        }
+        if reader.bytes(16)? != app_user_model {
+            continue;
+        }
+        while !reader.is_at_end() {
+            let value = reader.sized()?;
+            if value.is_empty() {
+                break;
+            }
+            let mut value = Reader {
+                data: value,
+                position: 4,
+            };
+            let id = value.u32()?;
+            value.bytes(1)?;
+            let value_type = value.u16()?;
+            value.bytes(2)?;
+            match (id, value_type) {
+                (APP_USER_MODEL_ID_PID, VT_LPWSTR) => {
+                    let len = value.u32()? as usize;
+                    let chars = utf16(value.bytes(len * 2)?);
+                    info.app_user_model_id = Some(chars.trim_end_matches('\0').to_string());
+                }
+                (TOAST_ACTIVATOR_CLSID_PID, VT_CLSID) => {
+                    info.toast_activator_clsid = Some(format_guid(value.bytes(16)?));
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Shell link to a local file written by [`write_shell_link`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShellLink {
+    /// Absolute path of the target.
+    pub target: String,
+    /// Description of the link.
+    pub name: Option<String>,
+    /// Working directory, the directory of the target when not set.
+    pub working_dir: Option<String>,
+    pub arguments: Option<String>,
+    pub icon_location: Option<String>,
+    pub icon_index: i32,
+    /// `System.AppUserModel.ID` property.
+    pub app_user_model_id: Option<String>,
+    /// `System.AppUserModel.ToastActivatorCLSID` property, a GUID with or without braces.
+    pub toast_activator_clsid: Option<String>,
+}
+
+impl ShellLink {
+    pub fn new(target: &str) -> ShellLink {
+        ShellLink {
+            target: target.to_string(),
+            ..ShellLink::default()
+        }
+    }
+}
+
+/// GUID in its binary form, the first three groups are little endian.
+fn guid_bytes(guid: &str) -> Result<[u8; 16], String> {
+    let guid = normalize_clsid(guid)?;
+    let hex: String = guid.chars().filter(|c| c.is_ascii_hexdigit()).collect();
+    let byte = |i: usize| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap_or_default();
+    let mut bytes = [0u8; 16];
+    for (i, source) in [3, 2, 1, 0, 5, 4, 7, 6].into_iter().enumerate() {
+        bytes[i] = byte(source);
+    }
+    for (i, item) in bytes.iter_mut().enumerate().skip(8) {
+        *item = byte(i);
+    }
+    Ok(bytes)
+}
+
+fn format_guid(bytes: &[u8]) -> String {
+    let hex = |indexes: &[usize]| -> String {
+        indexes
+            .iter()
+            .map(|i| format!("{:02X}", bytes[*i]))
+            .collect()
+    };
+    format!(
+        "{{{}-{}-{}-{}-{}}}",
+        hex(&[3, 2, 1, 0]),
+        hex(&[5, 4]),
+        hex(&[7, 6]),
+        hex(&[8, 9]),
+        hex(&[10, 11, 12, 13, 14, 15])
+    )
+}
+
+fn utf16_bytes(value: &str) -> Vec<u8> {
+    value.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+}
+
+/// StringData structure with UTF-16 characters.
+fn write_string_data(data: &mut Vec<u8>, value: &str) -> Result<(), String> {
+    let count = value.encode_utf16().count();
+    let count = u16::try_from(count).map_err(|_| format!("[{}] is too long", value))?;
+    data.extend(count.to_le_bytes());
+    data.extend(utf16_bytes(value));
+    Ok(())
+}
+
+/// LinkInfo structure with a fixed drive volume and the target as local base path, in single
+/// byte characters and in UTF-16.
+fn write_link_info(target: &str) -> Vec<u8> {
+    let mut volume_id = vec![];
+    for field in [0x11, DRIVE_FIXED, 0, 0x10] {
+        volume_id.extend(u32::to_le_bytes(field));
+    }
+    volume_id.push(0);
+    let mut base_path: Vec<u8> = target
+        .chars()
+        .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+        .collect();
+    base_path.push(0);
+    let mut unicode_base_path = utf16_bytes(target);
+    unicode_base_path.extend([0, 0]);
+    let volume_id_offset = LINK_INFO_HEADER_SIZE;
+    let base_path_offset = volume_id_offset + volume_id.len() as u32;
+    let suffix_offset = base_path_offset + base_path.len() as u32;
+    let unicode_base_path_offset = suffix_offset + 1;
+    let unicode_suffix_offset = unicode_base_path_offset + unicode_base_path.len() as u32;
+    let size = unicode_suffix_offset + 2;
+    let mut data = vec![];
+    for field in [
+        size,
+        LINK_INFO_HEADER_SIZE,
+        VOLUME_ID_AND_LOCAL_BASE_PATH,
+        volume_id_offset,
+        base_path_offset,
+        0,
+        suffix_offset,
+        unicode_base_path_offset,
+        unicode_suffix_offset,
+    ] {
+        data.extend(field.to_le_bytes());
+    }
+    data.extend(volume_id);
+    data.extend(base_path);
+    data.push(0);
+    data.extend(unicode_base_path);
+    data.extend([0, 0]);
+    data
+}
+
+/// Serialized property value with an integer name.
+fn property_value(id: u32, value_type: u16, value: &[u8]) -> Vec<u8> {
+    let size = 4 + 4 + 1 + 4 + value.len() as u32;
+    let mut data = size.to_le_bytes().to_vec();
+    data.extend(id.to_le_bytes());
+    data.push(0);
+    data.extend(value_type.to_le_bytes());
+    data.extend([0, 0]);
+    data.extend(value);
+    data
+}
+
+/// PropertyStoreDataBlock with the `System.AppUserModel` properties of the link, None when the
+/// link has none of them.
+fn write_property_store(link: &ShellLink) -> Result<Option<Vec<u8>>, String> {
+    let mut values = vec![];
+    if let Some(app_id) = &link.app_user_model_id {
+        let mut value = utf16_bytes(app_id);
+        value.extend([0, 0]);
+        let len = value.len() as u32 / 2;
+        value.resize(value.len().next_multiple_of(4), 0);
+        let value = [len.to_le_bytes().as_slice(), &value].concat();
+        values.extend(property_value(APP_USER_MODEL_ID_PID, VT_LPWSTR, &value));
+    }
+    if let Some(clsid) = &link.toast_activator_clsid {
+        let value = guid_bytes(clsid)?;
+        values.extend(property_value(TOAST_ACTIVATOR_CLSID_PID, VT_CLSID, &value));
+    }
+    if values.is_empty() {
+        return Ok(None);
+    }
+    let storage_size = 4 + 4 + 16 + values.len() as u32 + 4;
+    let mut block = (8 + storage_size + 4).to_le_bytes().to_vec();
+    block.extend(PROPERTY_STORE_SIGNATURE.to_le_bytes());
+    block.extend(storage_size.to_le_bytes());
+    block.extend(PROPERTY_STORAGE_VERSION.to_le_bytes());
+    block.extend(guid_bytes(APP_USER_MODEL_FORMAT_ID)?);
+    block.extend(values);
+    block.extend([0; 4]);
+    block.extend([0; 4]);
+    Ok(Some(block))
+}
+
+/// Writes a unicode shell link with link info, strings and the property store block.
+pub fn write_shell_link(link: &ShellLink) -> Result<Vec<u8>, String> {
+    let working_dir = link.working_dir.clone().or_else(|| {
+        link.target
+            .rsplit_once('\\')
+            .map(|(dir, _)| match dir.ends_with(':') {
+                true => format!("{}\\", dir),
+                false => dir.to_string(),
+            })
+    });
+    let strings = [
+        (HAS_NAME, &link.name),
+        (HAS_WORKING_DIR, &working_dir),
+        (HAS_ARGUMENTS, &link.arguments),
+        (HAS_ICON_LOCATION, &link.icon_location),
+    ];
+    let mut flags = HAS_LINK_INFO | IS_UNICODE;
+    for (flag, value) in strings.iter() {
+        if value.is_some() {
+            flags |= flag;
+        }
+    }
+    let mut data = vec![0u8; HEADER_SIZE];
+    data[0..4].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+    data[4..0x14].copy_from_slice(&LINK_CLSID);
+    data[0x14..0x18].copy_from_slice(&flags.to_le_bytes());
+    data[0x18..0x1C].copy_from_slice(&FILE_ATTRIBUTE_NORMAL.to_le_bytes());
+    data[0x38..0x3C].copy_from_slice(&link.icon_index.to_le_bytes());
+    data[0x3C..0x40].copy_from_slice(&SW_SHOWNORMAL.to_le_bytes());
+    data.extend(write_link_info(&link.target));
+    for (_, value) in strings.iter() {
+        if let Some(value) = value {
+            write_string_data(&mut data, value)?;
+        }
+    }
+    if let Some(block) = write_property_store(link)? {
+        data.extend(block);
+    }
+    data.extend([0; 4]);
+    Ok(data)
+}
+
+/// Link which replaces an existing shortcut of `wanted.target`, None when it is kept as it is.
+/// Shortcuts created by this tool are replaced by `wanted`. Other shortcuts without
+/// `System.AppUserModel.ID` which point to the same target get the application id and the
+/// activator, their description, arguments, icon and working directory are kept unless given.
+pub fn update_shell_link(existing: &ShellLinkInfo, wanted: &ShellLink) -> Option<ShellLink> {
+    let created_by_tool = existing.name.as_deref() == Some(SHORTCUT_DESCRIPTION);
+    let link = match created_by_tool {
+        true => wanted.clone(),
+        false if existing.app_user_model_id.is_some() => return None,
+        false => {
+            let same_target = existing
+                .local_base_path
+                .as_ref()
+                .is_some_and(|path| path.eq_ignore_ascii_case(&wanted.target));
+            if !same_target {
+                return None;
+            }
+            ShellLink {
+                name: existing.name.clone(),
+                working_dir: existing.working_dir.clone(),
+                arguments: wanted.arguments.clone().or(existing.arguments.clone()),
+                icon_location: wanted
+                    .icon_location
+                    .clone()
+                    .or(existing.icon_location.clone()),
+                ..wanted.clone()
+            }
+        }
+    };
+    let clsid = |clsid: &Option<String>| clsid.as_ref().and_then(|c| normalize_clsid(c).ok());
+    let unchanged = existing.local_base_path.as_deref() == Some(link.target.as_str())
+        && existing.arguments == link.arguments
+        && existing.icon_location == link.icon_location
+        && existing.app_user_model_id == link.app_user_model_id
+        && clsid(&existing.toast_activator_clsid) == clsid(&link.toast_activator_clsid);
+    (!unchanged).then_some(link)
+}
+
+#[cfg(test)]
+fn test_link(flags: u32, sections: &[&[u8]]) -> Vec<u8> {
+    let mut data = vec![0u8; HEADER_SIZE];
+    data[0] = HEADER_SIZE as u8;
+    data[0x14..0x18].copy_from_slice(&flags.to_le_bytes());
+    for section in sections {
+        data.extend_from_slice(section);
+    }
+    data
+}
+
+#[cfg(test)]
+fn test_string(value: &str) -> Vec<u8> {
+    let chars: Vec<u16> = value.encode_utf16().collect();
+    let mut data = (chars.len() as u16).to_le_bytes().to_vec();
+    data.extend(chars.iter().flat_map(|c| c.to_le_bytes()));
+    data
+}
+
+#[test]
+fn read_strings_test() {
+    let id_list = [4u8, 0, 1, 2, 3, 4];
+    let flags = HAS_LINK_TARGET_ID_LIST
+        | HAS_NAME
+        | HAS_RELATIVE_PATH
+        | HAS_WORKING_DIR
+        | HAS_ICON_LOCATION
+        | IS_UNICODE;
+    let data = test_link(
+        flags,
+        &[
+            &id_list,
+            &test_string("Created by win-toast-notifier"),
+            &test_string("./app.exe"),
+            &test_string(r"C:\App"),
+            &test_string(r"C:\App\app.ico"),
+        ],
+    );
+    let info = read_shell_link(&data).unwrap();
+    assert_eq!(info.name.as_deref(), Some("Created by win-toast-notifier"));
+    assert_eq!(info.arguments, None);
+    assert_eq!(info.icon_location.as_deref(), Some(r"C:\App\app.ico"));
+    assert_eq!(info.target().as_deref(), Some(r"C:\App\app.exe"));
+}
+
+#[test]
+fn read_link_info_test() {
+    let mut link_info = vec![0u8; 0x1C];
+    let base_offset = link_info.len() as u32;
+    link_info.extend_from_slice(b"C:\\App\\app.exe\0\0");
+    let size = link_info.len() as u32;
+    let fields = [
+        size,
+        0x1C,
+        VOLUME_ID_AND_LOCAL_BASE_PATH,
+        0,
+        base_offset,
+        0,
+        size - 1,
+    ];
+    for (i, field) in fields.iter().enumerate() {
+        link_info[i * 4..i * 4 + 4].copy_from_slice(&field.to_le_bytes());
+    }
+    let data = test_link(HAS_LINK_INFO | HAS_ARGUMENTS, &[&link_info, b"\x02\x00-a"]);
+    let info = read_shell_link(&data).unwrap();
+    assert_eq!(info.local_base_path.as_deref(), Some(r"C:\App\app.exe"));
+    assert_eq!(info.target().as_deref(), Some(r"C:\App\app.exe"));
+    assert_eq!(info.arguments.as_deref(), Some("-a"));
+}
+
+#[test]
+fn read_invalid_test() {
+    assert!(read_shell_link(b"not a link").is_err());
+    let data = test_link(HAS_NAME | IS_UNICODE, &[&[10, 0, 65, 0]]);
+    assert!(read_shell_link(&data).is_err());
+}
+
+#[test]
+fn write_header_test() {
+    let data = write_shell_link(&ShellLink {
+        icon_index: 2,
+        ..ShellLink::new(r"C:\App\app.exe")
+    })
+    .unwrap();
+    assert_eq!(data[0..4], [0x4C, 0, 0, 0]);
+    assert_eq!(data[4..0x14], LINK_CLSID);
+    let flags = HAS_LINK_INFO | HAS_WORKING_DIR | IS_UNICODE;
+    assert_eq!(data[0x14..0x18], flags.to_le_bytes());
+    assert_eq!(data[0x38..0x40], [2, 0, 0, 0, 1, 0, 0, 0]);
+    // Link has no properties, it ends with the terminal block right after the working dir.
+    let mut tail = test_string(r"C:\App");
+    tail.extend([0; 4]);
+    assert!(data.ends_with(&tail));
+}
+
+#[test]
+fn write_property_store_test() {
+    let link = ShellLink {
+        app_user_model_id: Some("App".into()),
+        ..ShellLink::new(r"C:\App\app.exe")
+    };
+    let data = write_shell_link(&link).unwrap();
+    #[rustfmt::skip]
+    let expected: &[u8] = &[
+        // PropertyStoreDataBlock size and signature.
+        0x41, 0, 0, 0, 0x09, 0, 0, 0xA0,
+        // Storage size, version and format id {9F4C2855-9F79-4B39-A8D0-E1D42DE1D5F3}.
+        0x35, 0, 0, 0, 0x31, 0x53, 0x50, 0x53,
+        0x55, 0x28, 0x4C, 0x9F, 0x79, 0x9F, 0x39, 0x4B,
+        0xA8, 0xD0, 0xE1, 0xD4, 0x2D, 0xE1, 0xD5, 0xF3,
+        // Value size, property id 5, reserved byte, VT_LPWSTR and padding.
+        0x19, 0, 0, 0, 5, 0, 0, 0, 0, 0x1F, 0, 0, 0,
+        // Length with the terminating null and "App".
+        4, 0, 0, 0, b'A', 0, b'p', 0, b'p', 0, 0, 0,
+        // End of values, end of storages and the terminal block.
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    assert!(data.ends_with(expected));
+}
+
+#[test]
+fn write_read_test() {
+    let link = ShellLink {
+        name: Some("Created by win-toast-notifier".into()),
+        arguments: Some("--flag \"a b\"".into()),
+        icon_location: Some(r"C:\App\app.ico".into()),
+        app_user_model_id: Some("com.app".into()),
+        toast_activator_clsid: Some("6b8a3b6f-1b4e-4a38-9b3d-0c2b0a1b2c3d".into()),
+        ..ShellLink::new(r"C:\Ünicode\app.exe")
+    };
+    let data = write_shell_link(&link).unwrap();
+    let info = read_shell_link(&data).unwrap();
+    assert_eq!(
+        info,
+        ShellLinkInfo {
+            local_base_path: Some(r"C:\Ünicode\app.exe".into()),
+            name: link.name.clone(),
+            relative_path: None,
+            working_dir: Some(r"C:\Ünicode".into()),
+            arguments: link.arguments.clone(),
+            icon_location: link.icon_location.clone(),
+            app_user_model_id: Some("com.app".into()),
+            toast_activator_clsid: Some("{6B8A3B6F-1B4E-4A38-9B3D-0C2B0A1B2C3D}".into()),
+        }
+    );
+    let clsid = [
+        0x6F, 0x3B, 0x8A, 0x6B, 0x4E, 0x1B, 0x38, 0x4A, 0x9B, 0x3D, 0x0C, 0x2B, 0x0A, 0x1B, 0x2C,
+        0x3D,
+    ];
+    assert!(data.windows(16).any(|window| window == clsid));
+}
+
+#[test]
+fn update_link_test() {
+    let wanted = ShellLink {
+        name: Some(SHORTCUT_DESCRIPTION.into()),
+        app_user_model_id: Some(r"C:\App\app.exe".into()),
+        toast_activator_clsid: Some("6b8a3b6f-1b4e-4a38-9b3d-0c2b0a1b2c3d".into()),
+        ..ShellLink::new(r"C:\App\app.exe")
+    };
+    let read = |link: &ShellLink| read_shell_link(&write_shell_link(link).unwrap()).unwrap();
+    let old_version = ShellLink {
+        name: Some(SHORTCUT_DESCRIPTION.into()),
+        ..ShellLink::new(r"C:\App\app.exe")
+    };
+    assert_eq!(
+        update_shell_link(&read(&old_version), &wanted),
+        Some(wanted.clone())
+    );
+    assert_eq!(update_shell_link(&read(&wanted), &wanted), None);
+    let own = ShellLink {
+        name: Some("My app".into()),
+        arguments: Some("--tray".into()),
+        ..ShellLink::new(r"C:\App\app.exe")
+    };
+    let updated = update_shell_link(&read(&own), &wanted).unwrap();
+    assert_eq!(updated.name.as_deref(), Some("My app"));
+    assert_eq!(updated.arguments.as_deref(), Some("--tray"));
+    assert_eq!(updated.app_user_model_id, wanted.app_user_model_id);
+    assert_eq!(update_shell_link(&read(&updated), &wanted), None);
+    let other_target = ShellLink::new(r"C:\Other\app.exe");
+    assert_eq!(update_shell_link(&read(&other_target), &wanted), None);
+    let with_id = ShellLink {
+        app_user_model_id: Some("com.other".into()),
+        ..own
+    };
+    assert_eq!(update_shell_link(&read(&with_id), &wanted), None);
+}
+
+#[test]
+fn write_invalid_test() {
+    let link = ShellLink {
+        toast_activator_clsid: Some("not a guid".into()),
+        ..ShellLink::new(r"C:\app.exe")
+    };
+    assert!(write_shell_link(&link).is_err());
+    let info = read_shell_link(&write_shell_link(&ShellLink::new(r"C:\app.exe")).unwrap());
+    assert_eq!(info.unwrap().working_dir.as_deref(), Some("C:\\"));
+    let link = ShellLink {
+        arguments: Some("a".repeat(70000)),
+        ..ShellLink::new(r"C:\app.exe")
+    };
+    assert!(write_shell_link(&link).is_err());
+}
@@ -0,0 +1,132 @@
+//! Manifest describing the application ids to register, read by `register --manifest`.
+//!
+//! ```toml
+//! [[app]]
+//! id = "com.company.tool"
+//! display_name = "Tool"
+//! icon = "icons/tool.svg"
+//! scope = "machine"
+//! ```
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::registration::{validate_app_id, AppRegistration, Scope};
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default, rename = "app")]
+    pub apps: Vec<ManifestApp>,
+}
+
+/// Application id of the manifest, values which are not given are left unchanged.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestApp {
+    pub id: String,
+    pub display_name: Option<String>,
+    /// Icon path, relative paths are resolved against the manifest directory.
+    pub icon: Option<String>,
+    pub icon_background_color: Option<String>,
+    pub show_in_settings: Option<bool>,
+    pub custom_activator: Option<String>,
+    pub launch_command: Option<String>,
+    /// Scope of the registration, user by default.
+    #[serde(default)]
+    pub scope: Scope,
+}
+
+impl ManifestApp {
+    pub fn registration(&self) -> AppRegistration {
+        AppRegistration {
+            app_id: self.id.clone(),
+            display_name: self.display_name.clone(),
+            icon_path: self.icon.clone(),
+            icon_background_color: self.icon_background_color.clone(),
+            show_in_settings: self.show_in_settings,
+            custom_activator: self.custom_activator.clone(),
+            launch_command: self.launch_command.clone(),
+        }
+    }
+}
+
+impl Manifest {
+    /// Registrations of the applications in `scope`.
+    pub fn registrations(&self, scope: Scope) -> Vec<AppRegistration> {
+        self.apps
+            .iter()
+            .filter(|app| app.scope == scope)
+            .map(|app| app.registration())
+            .collect()
+    }
+}
+
+/// Parses the manifest, icon paths are resolved against `base_dir`.
+pub fn parse_manifest(text: &str, base_dir: &Path) -> Result<Manifest, String> {
+    let mut manifest: Manifest =
+        toml::from_str(text).map_err(|e| format!("Invalid manifest: {}", e))?;
+    let mut listed = HashSet::new();
+    for app in manifest.apps.iter_mut() {
+        validate_app_id(&app.id)?;
+        if !listed.insert((app.id.to_lowercase(), app.scope)) {
+            return Err(format!(
+                "app id [{}] is listed more than once in {} scope",
+                app.id, app.scope
+            ));
+        }
+        if let Some(icon) = &app.icon {
+            app.icon = Some(base_dir.join(icon).display().to_string());
+        }
+    }
+    Ok(manifest)
+}
+
+#[test]
+fn parse_manifest_test() {
+    let text = r#"
+        [[app]]
+        id = "com.tool"
+        display_name = "Tool"
+        icon = "icons/tool.svg"
+
+        [[app]]
+        id = "com.tool"
+        scope = "machine"
+        show_in_settings = false
+    "#;
+    let manifest = parse_manifest(text, Path::new("base")).unwrap();
+    let icon = Path::new("base").join("icons/tool.svg");
+    assert_eq!(
+        manifest.registrations(Scope::User),
+        vec![AppRegistration {
+            display_name: Some("Tool".into()),
+            icon_path: Some(icon.display().to_string()),
+            ..AppRegistration::new("com.tool")
+        }]
+    );
+    assert_eq!(
+        manifest.registrations(Scope::Machine),
+        vec![AppRegistration {
+            show_in_settings: Some(false),
+            ..AppRegistration::new("com.tool")
+        }]
+    );
+    assert_eq!(parse_manifest("", Path::new("")), Ok(Manifest::default()));
+}
+
+#[test]
+fn parse_invalid_manifest_test() {
+    let invalid = [
+        "[[app]]\nid = 'a'\n[[app]]\nid = 'A'",
+        "[[app]]\nid = 'a/b'",
+        "[[app]]\nid = 'a'\nscope = 'system'",
+        "[[app]]\nid = 'a'\nname = 'typo'",
+        "[[app]]\ndisplay_name = 'no id'",
+    ];
+    for text in invalid {
+        assert!(parse_manifest(text, Path::new("")).is_err(), "{}", text);
+    }
+}
@@ -0,0 +1,197 @@
+//! Notification statuses published to status-stream subscribers.
+
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::time::Instant;
+
+use crate::ring_buffer::ApproximateSize;
+
+lazy_static! {
+    static ref START_TIME: Instant = Instant::now();
+}
+
+/// Starts the clock used for [`StatusEvent::monotonic_ms`], it is started by the first event otherwise.
+pub fn start_clock() {
+    lazy_static::initialize(&START_TIME);
+}
+
+/// Arguments of the activated toast and values of its inputs.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NotificationActivationInfo {
+    pub arguments: String,
+    pub inputs: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DismissReason {
+    UserCanceled,
+    ApplicationHidden,
+    TimedOut,
+}
+
+/// Status change of a notification, the first field is the notification id.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum NotificationStatus {
+    /// Toast was shown.
+    Shown(String),
+    /// Toast was added to the schedule and is shown at the given time.
+    Scheduled(String, DateTime<Utc>),
+    Activated(String, NotificationActivationInfo),
+    Dismissed(String, DismissReason),
+    DismissedError(String, String),
+    Failed(String, String),
+    /// Toast was removed by hide or hide all request.
+    HiddenByApi(String),
+    /// Toast was replaced by the notification with the same tag and group, the second field is its id.
+    Replaced(String, String),
+    /// Expiration time of the toast passed.
+    Expired(String),
+}
+
+/// Notification status with the context it was recorded in.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatusEvent {
+    pub status: NotificationStatus,
+    /// Wall-clock time the status was recorded.
+    pub timestamp: DateTime<Utc>,
+    /// Milliseconds since the process started, not affected by system clock changes.
+    pub monotonic_ms: u64,
+    pub application_id: String,
+    /// Time the notification was shown.
+    pub created_at: DateTime<Utc>,
+}
+
+impl StatusEvent {
+    pub fn new(
+        status: NotificationStatus,
+        application_id: &str,
+        created_at: DateTime<Utc>,
+    ) -> StatusEvent {
+        StatusEvent {
+            status,
+            timestamp: Utc::now(),
+            monotonic_ms: START_TIME.elapsed().as_millis() as u64,
+            application_id: application_id.to_string(),
+            created_at,
+        }
+    }
+}
+
+impl ApproximateSize for StatusEvent {
+    fn approximate_size(&self) -> usize {
+        let strings = match &self.status {
+            NotificationStatus::Shown(id)
+            | NotificationStatus::Scheduled(id, _)
+            | NotificationStatus::Dismissed(id, _)
+            | NotificationStatus::HiddenByApi(id)
+            | NotificationStatus::Expired(id) => id.len(),
+            NotificationStatus::Activated(id, info) => {
+                id.len()
+                    + info.arguments.len()
+                    + info
+                        .inputs
+                        .iter()
+                        .map(|(key, value)| size_of::<(String, String)>() + key.len() + value.len())
+                        .sum::<usize>()
+            }
+            NotificationStatus::DismissedError(id, text)
+            | NotificationStatus::Failed(id, text)
+            | NotificationStatus::Replaced(id, text) => id.len() + text.len(),
+        };
+        size_of::<StatusEvent>() + self.application_id.len() + strings
+    }
+}
+
+/// Message of the status stream for event with the given number,
+/// see `doc/status-stream.schema.json` for the format.
+pub fn stream_message(number: usize, event: &StatusEvent) -> Value {
+    let mut message = match &event.status {
+        NotificationStatus::Shown(id) => json!({
+            "id": id,
+            "type": "Shown"
+        }),
+        NotificationStatus::Scheduled(id, deliver_at) => json!({
+            "id": id,
+            "deliverAt": deliver_at,
+            "type": "Scheduled"
+        }),
+        NotificationStatus::Activated(id, info) => json!({
+            "id": id,
+            "info": info,
+            "type": "Activated"
+        }),
+        NotificationStatus::Dismissed(id, reason) => json!({
+            "id": id,
+            "dismissReason": reason,
+            "type": "Dismissed"
+        }),
+        NotificationStatus::DismissedError(id, msg) => json!({
+            "id": id,
+            "description": msg,
+            "type": "DismissedError"
+        }),
+        NotificationStatus::Failed(id, msg) => json!({
+            "id": id,
+            "description": msg,
+            "type": "Failed"
+        }),
+        NotificationStatus::HiddenByApi(id) => json!({
+            "id": id,
+            "type": "HiddenByApi"
+        }),
+        NotificationStatus::Replaced(id, replaced_by) => json!({
+            "id": id,
+            "replacedBy": replaced_by,
+            "type": "Replaced"
+        }),
+        NotificationStatus::Expired(id) => json!({
+            "id": id,
+            "type": "Expired"
+        }),
+    };
+    message["number"] = json!(number);
+    message["timestamp"] = json!(event.timestamp);
+    message["monotonicMs"] = json!(event.monotonic_ms);
+    message["applicationId"] = json!(event.application_id);
+    message["createdAt"] = json!(event.created_at);
+    message
+}
+
+#[test]
+fn stream_message_test() {
+    let event = StatusEvent::new(
+        NotificationStatus::Replaced("old".to_string(), "new".to_string()),
+        "app",
+        Utc::now(),
+    );
+    let message = stream_message(7, &event);
+    assert_eq!(message["number"], 7);
+    assert_eq!(message["id"], "old");
+    assert_eq!(message["replacedBy"], "new");
+    assert_eq!(message["type"], "Replaced");
+    assert_eq!(message["applicationId"], "app");
+    let deliver_at = Utc::now();
+    let event = StatusEvent::new(
+        NotificationStatus::Scheduled("id".to_string(), deliver_at),
+        "app",
+        Utc::now(),
+    );
+    assert_eq!(stream_message(0, &event)["deliverAt"], json!(deliver_at));
+}
+
+#[test]
+fn status_event_serialization_test() {
+    start_clock();
+    let event = StatusEvent::new(
+        NotificationStatus::Dismissed("id".to_string(), DismissReason::UserCanceled),
+        "app",
+        Utc::now(),
+    );
+    let json = serde_json::to_string(&event).unwrap();
+    assert_eq!(serde_json::from_str::<StatusEvent>(&json).unwrap(), event);
+    assert!(event.timestamp >= event.created_at);
+}
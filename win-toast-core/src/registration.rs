@@ -0,0 +1,866 @@
+//! Planning of application id registrations.
+//!
+//! [`plan_register`] and [`plan_unregister`] compare the wanted registration with a [`Registry`]
+//! and return the [`Plan`] of changes, which is printed as a diff by `--dry-run` or applied.
+
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::registry::{RegValue, Registry};
+
+/// Key containing application id registrations.
+pub const APP_USER_MODEL_ID: &str = "AppUserModelId";
+
+/// Value marking registrations created by this tool.
+pub const CREATED_BY_VALUE: &str = "CreatedBy";
+
+/// Name of this tool in [`CREATED_BY_VALUE`].
+pub const CREATED_BY: &str = "win-toast-notifier";
+
+/// Description of shortcuts created by this tool.
+pub const SHORTCUT_DESCRIPTION: &str = "Created by win-toast-notifier";
+
+/// Registry hive of a registration. User registrations need no admin rights.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// `HKEY_CURRENT_USER\Software\Classes`
+    #[default]
+    User,
+    /// `HKEY_LOCAL_MACHINE\SOFTWARE\Classes`
+    Machine,
+}
+
+impl Scope {
+    /// Hive and path of the classes key of the scope.
+    pub fn classes_key(&self) -> (&'static str, &'static str) {
+        match self {
+            Scope::User => ("HKEY_CURRENT_USER", r"Software\Classes"),
+            Scope::Machine => ("HKEY_LOCAL_MACHINE", r"SOFTWARE\Classes"),
+        }
+    }
+
+    /// Full name of the classes key, such as `HKEY_CURRENT_USER\Software\Classes`.
+    pub fn root_name(&self) -> String {
+        let (hive, path) = self.classes_key();
+        format!(r"{}\{}", hive, path)
+    }
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scope::User => write!(f, "user"),
+            Scope::Machine => write!(f, "machine"),
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(Scope::User),
+            "machine" => Ok(Scope::Machine),
+            _ => Err(format!(
+                "[{}] is not a valid scope, expected user or machine",
+                s
+            )),
+        }
+    }
+}
+
+/// Single change of a registration.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    CreateKey(String),
+    SetValue {
+        key: String,
+        name: String,
+        old: Option<RegValue>,
+        new: RegValue,
+    },
+    DeleteValue {
+        key: String,
+        name: String,
+        old: RegValue,
+    },
+    /// Deletes key with its subkeys, `values` are the deleted values of the key.
+    DeleteKey {
+        key: String,
+        values: Vec<(String, RegValue)>,
+    },
+    CreateFile(String),
+    DeleteFile(String),
+}
+
+/// Changes of the registry below `root` and of files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+    pub root: String,
+    pub changes: Vec<Change>,
+}
+
+/// Error of a change which could not be applied.
+#[derive(Debug)]
+pub struct ApplyError {
+    pub error: io::Error,
+    /// Key or value the change was applied to.
+    pub target: String,
+}
+
+impl Plan {
+    pub fn new(root: &str) -> Plan {
+        Plan {
+            root: root.to_string(),
+            changes: vec![],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Applies registry changes in order, file changes are left to the caller.
+    pub fn apply(&self, registry: &mut dyn Registry) -> Result<(), ApplyError> {
+        for change in self.changes.iter() {
+            let (result, target) = match change {
+                Change::CreateKey(key) => (registry.create_key(key), key.clone()),
+                Change::SetValue { key, name, new, .. } => (
+                    registry.set_value(key, name, new),
+                    format!(r"{}\{}", key, name),
+                ),
+                Change::DeleteValue { key, name, .. } => (
+                    registry.delete_value(key, name),
+                    format!(r"{}\{}", key, name),
+                ),
+                Change::DeleteKey { key, .. } => (registry.delete_key(key), key.clone()),
+                Change::CreateFile(_) | Change::DeleteFile(_) => continue,
+            };
+            result.map_err(|error| ApplyError { error, target })?;
+        }
+        Ok(())
+    }
+}
+
+/// Diff of the plan: `+` lines are created, `-` lines deleted, overwritten values have both
+/// and keys of changed values are given as `  [key]` context lines.
+impl Display for Plan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.changes.is_empty() {
+            return writeln!(f, "No changes");
+        }
+        let mut current_key: Option<&String> = None;
+        for change in self.changes.iter() {
+            match change {
+                Change::CreateKey(key) => {
+                    writeln!(f, r"+ [{}\{}]", self.root, key)?;
+                    current_key = Some(key);
+                }
+                Change::SetValue {
+                    key,
+                    name,
+                    old,
+                    new,
+                } => {
+                    if current_key != Some(key) {
+                        writeln!(f, r"  [{}\{}]", self.root, key)?;
+                        current_key = Some(key);
+                    }
+                    if let Some(old) = old {
+                        writeln!(f, "- {} = {}", value_name(name), old)?;
+                    }
+                    writeln!(f, "+ {} = {}", value_name(name), new)?;
+                }
+                Change::DeleteValue { key, name, old } => {
+                    if current_key != Some(key) {
+                        writeln!(f, r"  [{}\{}]", self.root, key)?;
+                        current_key = Some(key);
+                    }
+                    writeln!(f, "- {} = {}", value_name(name), old)?;
+                }
+                Change::DeleteKey { key, values } => {
+                    writeln!(f, r"- [{}\{}]", self.root, key)?;
+                    for (name, value) in values {
+                        writeln!(f, "- {} = {}", value_name(name), value)?;
+                    }
+                    current_key = None;
+                }
+                Change::CreateFile(path) => writeln!(f, "+ file {}", path)?,
+                Change::DeleteFile(path) => writeln!(f, "- file {}", path)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Name of the value as in `.reg` files, `@` for the default value.
+fn value_name(name: &str) -> &str {
+    match name {
+        "" => "@",
+        name => name,
+    }
+}
+
+/// Maximum length of an application id in characters.
+pub const MAX_APP_ID_LEN: usize = 128;
+
+/// Checks rules of application ids which Windows rejects: empty or longer than
+/// [`MAX_APP_ID_LEN`] ids and ids with slashes.
+pub fn validate_app_id(app_id: &str) -> Result<(), String> {
+    if app_id.is_empty() {
+        return Err("app id is empty".into());
+    }
+    if app_id.chars().count() > MAX_APP_ID_LEN {
+        return Err(format!(
+            "app id [{}] is longer than {} characters",
+            app_id, MAX_APP_ID_LEN
+        ));
+    }
+    if app_id.contains('\\') || app_id.contains('/') {
+        return Err(format!("app id [{}] contains invalid characters", app_id));
+    }
+    Ok(())
+}
+
+fn validate_icon_path(icon_path: &str) -> Result<(), String> {
+    match Path::new(icon_path).extension() {
+        Some(ext) if ext.eq_ignore_ascii_case("png") => Ok(()),
+        _ => Err("Icon path should have png extension.".into()),
+    }
+}
+
+/// Converts `#RRGGBB`, `RRGGBB` or `AARRGGBB` to the `AARRGGBB` form used by `IconBackgroundColor`.
+pub fn normalize_color(color: &str) -> Result<String, String> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    let valid = hex.chars().all(|c| c.is_ascii_hexdigit());
+    match hex.len() {
+        6 if valid => Ok(format!("FF{}", hex.to_uppercase())),
+        8 if valid => Ok(hex.to_uppercase()),
+        _ => Err(format!(
+            "Icon background color [{}] should be #RRGGBB or AARRGGBB.",
+            color
+        )),
+    }
+}
+
+/// Converts GUID with or without braces to the `{XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX}` form.
+pub fn normalize_clsid(clsid: &str) -> Result<String, String> {
+    let guid = clsid
+        .strip_prefix('{')
+        .and_then(|guid| guid.strip_suffix('}'))
+        .unwrap_or(clsid);
+    let groups: Vec<&str> = guid.split('-').collect();
+    let valid = groups.iter().map(|group| group.len()).eq([8, 4, 4, 4, 12])
+        && groups
+            .iter()
+            .all(|group| group.chars().all(|c| c.is_ascii_hexdigit()));
+    match valid {
+        true => Ok(format!("{{{}}}", guid.to_uppercase())),
+        false => Err(format!(
+            "Custom activator [{}] should be a GUID like {{XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX}}.",
+            clsid
+        )),
+    }
+}
+
+/// Key of the application id registration.
+pub fn app_id_key(app_id: &str) -> String {
+    format!(r"{}\{}", APP_USER_MODEL_ID, app_id)
+}
+
+/// Key of the COM class with the given CLSID.
+pub fn clsid_key(clsid: &str) -> String {
+    format!(r"CLSID\{}", clsid)
+}
+
+/// Key with the command which starts the COM activator with the given CLSID.
+pub fn launch_command_key(clsid: &str) -> String {
+    format!(r"{}\LocalServer32", clsid_key(clsid))
+}
+
+/// Values of an application id registration, values which are not given are left unchanged.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AppRegistration {
+    pub app_id: String,
+    /// Notification header, the application id when it is not registered yet.
+    pub display_name: Option<String>,
+    pub icon_path: Option<String>,
+    /// Background color of the icon, see [`normalize_color`].
+    pub icon_background_color: Option<String>,
+    /// Whether the application is listed in notification settings.
+    pub show_in_settings: Option<bool>,
+    /// CLSID of the COM server activated when a toast is clicked.
+    pub custom_activator: Option<String>,
+    /// Command line starting the COM server, requires `custom_activator`.
+    pub launch_command: Option<String>,
+}
+
+impl AppRegistration {
+    pub fn new(app_id: &str) -> AppRegistration {
+        AppRegistration {
+            app_id: app_id.to_string(),
+            ..AppRegistration::default()
+        }
+    }
+}
+
+/// Adds the change which sets the value unless it is already set.
+fn plan_value(
+    registry: &dyn Registry,
+    plan: &mut Plan,
+    key: &str,
+    key_exists: bool,
+    name: &str,
+    new: RegValue,
+) -> Result<(), String> {
+    let old = match key_exists {
+        true => registry.get_value(key, name).map_err(|e| e.to_string())?,
+        false => None,
+    };
+    if old.as_ref() != Some(&new) {
+        plan.changes.push(Change::SetValue {
+            key: key.to_string(),
+            name: name.to_string(),
+            old,
+            new,
+        });
+    }
+    Ok(())
+}
+
+/// Adds the changes which create the key with the [`CREATED_BY_VALUE`] marker.
+fn create_key(plan: &mut Plan, key: &str) {
+    plan.changes.push(Change::CreateKey(key.to_string()));
+    plan.changes.push(Change::SetValue {
+        key: key.to_string(),
+        name: CREATED_BY_VALUE.to_string(),
+        old: None,
+        new: RegValue::String(CREATED_BY.to_string()),
+    });
+}
+
+/// Key exists and has the [`CREATED_BY_VALUE`] marker of this tool.
+fn is_created_by_tool(registry: &dyn Registry, key: &str) -> Result<bool, String> {
+    let value = registry
+        .get_value(key, CREATED_BY_VALUE)
+        .map_err(|e| e.to_string())?;
+    Ok(value == Some(RegValue::String(CREATED_BY.to_string())))
+}
+
+/// Changes which register the application id, values which are already set are not changed.
+/// A launch command which this tool did not create is not replaced.
+pub fn plan_register(
+    registry: &dyn Registry,
+    registration: &AppRegistration,
+) -> Result<Plan, String> {
+    let app_id = registration.app_id.as_str();
+    validate_app_id(app_id)?;
+    if let Some(icon_path) = &registration.icon_path {
+        validate_icon_path(icon_path)?;
+    }
+    let color = match &registration.icon_background_color {
+        Some(color) => Some(normalize_color(color)?),
+        None => None,
+    };
+    let clsid = match &registration.custom_activator {
+        Some(clsid) => Some(normalize_clsid(clsid)?),
+        None => None,
+    };
+    if registration.launch_command.is_some() && clsid.is_none() {
+        return Err("Launch command requires custom activator.".into());
+    }
+    let mut plan = Plan::new(&registry.root_name());
+    let key = app_id_key(app_id);
+    let exists = registry.key_exists(&key).map_err(|e| e.to_string())?;
+    if !exists {
+        create_key(&mut plan, &key);
+    }
+    let display_name = match (&registration.display_name, exists) {
+        (Some(display_name), _) => Some(display_name.clone()),
+        (None, false) => Some(app_id.to_string()),
+        (None, true) => None,
+    };
+    let values = [
+        ("DisplayName", display_name.map(RegValue::String)),
+        (
+            "IconUri",
+            registration.icon_path.clone().map(RegValue::String),
+        ),
+        ("IconBackgroundColor", color.map(RegValue::String)),
+        (
+            "ShowInSettings",
+            registration
+                .show_in_settings
+                .map(|show| RegValue::Dword(show as u32)),
+        ),
+        ("CustomActivator", clsid.clone().map(RegValue::String)),
+    ];
+    for (name, value) in values {
+        if let Some(value) = value {
+            plan_value(registry, &mut plan, &key, exists, name, value)?;
+        }
+    }
+    if let (Some(clsid), Some(command)) = (clsid, &registration.launch_command) {
+        let key = launch_command_key(&clsid);
+        let exists = registry.key_exists(&key).map_err(|e| e.to_string())?;
+        if !exists {
+            let class_key = clsid_key(&clsid);
+            if !registry.key_exists(&class_key).map_err(|e| e.to_string())? {
+                create_key(&mut plan, &class_key);
+            }
+            create_key(&mut plan, &key);
+        }
+        let command = RegValue::String(command.clone());
+        if exists && !is_created_by_tool(registry, &key)? {
+            let current = registry.get_value(&key, "").map_err(|e| e.to_string())?;
+            if current.as_ref() != Some(&command) {
+                return Err(format!(
+                    "Launch command of activator {} was registered by another product, \
+                     remove its LocalServer32 key or register without launch command.",
+                    clsid
+                ));
+            }
+        }
+        plan_value(registry, &mut plan, &key, exists, "", command)?;
+    }
+    Ok(plan)
+}
+
+/// Changes which remove the application id registration and the launch command of its
+/// custom activator, empty when it is not registered. Activator keys are removed only when this
+/// tool created them, the class key only when it has no other subkeys.
+pub fn plan_unregister(registry: &dyn Registry, app_id: &str) -> Result<Plan, String> {
+    validate_app_id(app_id)?;
+    let mut plan = Plan::new(&registry.root_name());
+    let key = app_id_key(app_id);
+    if !registry.key_exists(&key).map_err(|e| e.to_string())? {
+        return Ok(plan);
+    }
+    let values = registry.values(&key).map_err(|e| e.to_string())?;
+    let clsid = values.iter().find_map(|(name, value)| match value {
+        RegValue::String(clsid) if name.eq_ignore_ascii_case("CustomActivator") => {
+            normalize_clsid(clsid).ok()
+        }
+        _ => None,
+    });
+    plan.changes.push(Change::DeleteKey { key, values });
+    let Some(clsid) = clsid else {
+        return Ok(plan);
+    };
+    let command_key = launch_command_key(&clsid);
+    if !is_created_by_tool(registry, &command_key)? {
+        return Ok(plan);
+    }
+    let values = registry.values(&command_key).map_err(|e| e.to_string())?;
+    plan.changes.push(Change::DeleteKey {
+        key: command_key,
+        values,
+    });
+    let key = clsid_key(&clsid);
+    let subkeys = registry.subkeys(&key).map_err(|e| e.to_string())?;
+    let other_subkeys = subkeys
+        .iter()
+        .any(|subkey| !subkey.eq_ignore_ascii_case("LocalServer32"));
+    if is_created_by_tool(registry, &key)? && !other_subkeys {
+        let values = registry.values(&key).map_err(|e| e.to_string())?;
+        plan.changes.push(Change::DeleteKey { key, values });
+    }
+    Ok(plan)
+}
+
+/// Application ids created by this tool which are not in `registrations`.
+pub fn unlisted_app_ids(
+    registry: &dyn Registry,
+    registrations: &[AppRegistration],
+) -> Result<Vec<String>, String> {
+    let mut app_ids = vec![];
+    let created_by = RegValue::String(CREATED_BY.to_string());
+    for app_id in registry
+        .subkeys(APP_USER_MODEL_ID)
+        .map_err(|e| e.to_string())?
+    {
+        let listed = registrations
+            .iter()
+            .any(|registration| registration.app_id.eq_ignore_ascii_case(&app_id));
+        let value = registry
+            .get_value(&app_id_key(&app_id), CREATED_BY_VALUE)
+            .map_err(|e| e.to_string())?;
+        if !listed && value.as_ref() == Some(&created_by) {
+            app_ids.push(app_id);
+        }
+    }
+    Ok(app_ids)
+}
+
+/// Application ids whose `IconUri` is `icon_path`, paths are compared ignoring case.
+pub fn icon_users(registry: &dyn Registry, icon_path: &str) -> Result<Vec<String>, String> {
+    let mut app_ids = vec![];
+    for app_id in registry
+        .subkeys(APP_USER_MODEL_ID)
+        .map_err(|e| e.to_string())?
+    {
+        let value = registry
+            .get_value(&app_id_key(&app_id), "IconUri")
+            .map_err(|e| e.to_string())?;
+        if matches!(value, Some(RegValue::String(icon)) if icon.eq_ignore_ascii_case(icon_path)) {
+            app_ids.push(app_id);
+        }
+    }
+    Ok(app_ids)
+}
+
+/// Changes which register all `registrations` and unregister `remove`, empty when the registry
+/// already matches.
+pub fn plan_sync(
+    registry: &dyn Registry,
+    registrations: &[AppRegistration],
+    remove: &[String],
+) -> Result<Plan, String> {
+    let mut plan = Plan::new(&registry.root_name());
+    for registration in registrations {
+        plan.changes
+            .extend(plan_register(registry, registration)?.changes);
+    }
+    for app_id in remove {
+        plan.changes
+            .extend(plan_unregister(registry, app_id)?.changes);
+    }
+    Ok(plan)
+}
+
+#[cfg(test)]
+use crate::registry::MemoryRegistry;
+
+#[cfg(test)]
+fn with_icon(app_id: &str, icon_path: &str) -> AppRegistration {
+    AppRegistration {
+        icon_path: Some(icon_path.into()),
+        ..AppRegistration::new(app_id)
+    }
+}
+
+#[test]
+fn normalize_test() {
+    assert_eq!(normalize_color("#1a2b3c").unwrap(), "FF1A2B3C");
+    assert_eq!(normalize_color("801a2b3c").unwrap(), "801A2B3C");
+    assert_eq!(
+        normalize_clsid("c3d7a5e8-0f3b-4d6c-9a8e-2b1f0e7d6c5a").unwrap(),
+        "{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}"
+    );
+    assert_eq!(
+        normalize_clsid("{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}").unwrap(),
+        "{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}"
+    );
+}
+
+#[test]
+fn register_activator_test() {
+    let mut registry = MemoryRegistry::new("HKEY_CLASSES_ROOT");
+    let registration = AppRegistration {
+        icon_background_color: Some("#1a2b3c".into()),
+        show_in_settings: Some(false),
+        custom_activator: Some("c3d7a5e8-0f3b-4d6c-9a8e-2b1f0e7d6c5a".into()),
+        launch_command: Some(r#""C:\App\app.exe" -ToastActivated"#.into()),
+        ..AppRegistration::new("App")
+    };
+    let plan = plan_register(&registry, &registration).unwrap();
+    assert_eq!(
+        plan.to_string(),
+        concat!(
+            "+ [HKEY_CLASSES_ROOT\\AppUserModelId\\App]\n",
+            "+ CreatedBy = \"win-toast-notifier\"\n",
+            "+ DisplayName = \"App\"\n",
+            "+ IconBackgroundColor = \"FF1A2B3C\"\n",
+            "+ ShowInSettings = dword:00000000\n",
+            "+ CustomActivator = \"{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}\"\n",
+            "+ [HKEY_CLASSES_ROOT\\CLSID\\{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}]\n",
+            "+ CreatedBy = \"win-toast-notifier\"\n",
+            "+ [HKEY_CLASSES_ROOT\\CLSID\\{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}\\LocalServer32]\n",
+            "+ CreatedBy = \"win-toast-notifier\"\n",
+            "+ @ = \"\"C:\\App\\app.exe\" -ToastActivated\"\n",
+        )
+    );
+    plan.apply(&mut registry).unwrap();
+    assert!(plan_register(&registry, &registration).unwrap().is_empty());
+    let registration = AppRegistration {
+        show_in_settings: Some(true),
+        ..AppRegistration::new("App")
+    };
+    let plan = plan_register(&registry, &registration).unwrap();
+    assert_eq!(
+        plan.to_string(),
+        concat!(
+            "  [HKEY_CLASSES_ROOT\\AppUserModelId\\App]\n",
+            "- ShowInSettings = dword:00000000\n",
+            "+ ShowInSettings = dword:00000001\n",
+        )
+    );
+    let plan = plan_unregister(&registry, "App").unwrap();
+    assert_eq!(plan.changes.len(), 3);
+    plan.apply(&mut registry).unwrap();
+    assert!(!registry
+        .key_exists(r"CLSID\{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}")
+        .unwrap());
+}
+
+#[test]
+fn register_plan_test() {
+    let mut registry = MemoryRegistry::new("HKEY_CLASSES_ROOT");
+    let plan = plan_register(&registry, &with_icon("App", r"C:\app.png")).unwrap();
+    assert_eq!(
+        plan.to_string(),
+        concat!(
+            "+ [HKEY_CLASSES_ROOT\\AppUserModelId\\App]\n",
+            "+ CreatedBy = \"win-toast-notifier\"\n",
+            "+ DisplayName = \"App\"\n",
+            "+ IconUri = \"C:\\app.png\"\n"
+        )
+    );
+    plan.apply(&mut registry).unwrap();
+    let plan = plan_register(&registry, &with_icon("App", r"C:\app.png")).unwrap();
+    assert!(plan.is_empty());
+    assert_eq!(plan.to_string(), "No changes\n");
+    let registration = AppRegistration {
+        display_name: Some("My app".into()),
+        ..AppRegistration::new("App")
+    };
+    let plan = plan_register(&registry, &registration).unwrap();
+    assert_eq!(
+        plan.to_string(),
+        concat!(
+            "  [HKEY_CLASSES_ROOT\\AppUserModelId\\App]\n",
+            "- DisplayName = \"App\"\n",
+            "+ DisplayName = \"My app\"\n"
+        )
+    );
+    plan.apply(&mut registry).unwrap();
+    assert_eq!(
+        registry
+            .get_value(&app_id_key("App"), "DisplayName")
+            .unwrap(),
+        Some(RegValue::String("My app".into()))
+    );
+}
+
+#[test]
+fn register_validation_test() {
+    let registry = MemoryRegistry::new("HKEY_CLASSES_ROOT");
+    assert!(plan_register(&registry, &AppRegistration::new(r"a\b")).is_err());
+    assert!(plan_register(&registry, &AppRegistration::new("")).is_err());
+    let long = "a".repeat(MAX_APP_ID_LEN + 1);
+    assert!(plan_register(&registry, &AppRegistration::new(&long)).is_err());
+    assert!(plan_register(&registry, &with_icon("App", "icon.ico")).is_err());
+    assert!(plan_register(&registry, &with_icon("App", r"C:\App.PNG")).is_ok());
+    assert!(plan_unregister(&registry, "a/b").is_err());
+    for color in ["#12345", "12345G", "#1234567890"] {
+        let registration = AppRegistration {
+            icon_background_color: Some(color.into()),
+            ..AppRegistration::new("App")
+        };
+        assert!(
+            plan_register(&registry, &registration).is_err(),
+            "{}",
+            color
+        );
+    }
+    for clsid in [
+        "{1234}",
+        "C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5",
+        "not a guid",
+    ] {
+        let registration = AppRegistration {
+            custom_activator: Some(clsid.into()),
+            ..AppRegistration::new("App")
+        };
+        assert!(
+            plan_register(&registry, &registration).is_err(),
+            "{}",
+            clsid
+        );
+    }
+    let registration = AppRegistration {
+        launch_command: Some("app.exe".into()),
+        ..AppRegistration::new("App")
+    };
+    assert!(plan_register(&registry, &registration).is_err());
+}
+
+#[test]
+fn unregister_plan_test() {
+    let mut registry = MemoryRegistry::new("HKEY_CLASSES_ROOT");
+    assert!(plan_unregister(&registry, "App").unwrap().is_empty());
+    plan_register(&registry, &AppRegistration::new("App"))
+        .unwrap()
+        .apply(&mut registry)
+        .unwrap();
+    let mut plan = plan_unregister(&registry, "App").unwrap();
+    plan.changes
+        .push(Change::DeleteFile(r"C:\Start Menu\App.lnk".into()));
+    assert_eq!(
+        plan.to_string(),
+        concat!(
+            "- [HKEY_CLASSES_ROOT\\AppUserModelId\\App]\n",
+            "- CreatedBy = \"win-toast-notifier\"\n",
+            "- DisplayName = \"App\"\n",
+            "- file C:\\Start Menu\\App.lnk\n"
+        )
+    );
+    plan.apply(&mut registry).unwrap();
+    assert!(!registry.key_exists(&app_id_key("App")).unwrap());
+    assert!(registry.key_exists(APP_USER_MODEL_ID).unwrap());
+}
+
+#[test]
+fn unregister_foreign_activator_test() {
+    let mut registry = MemoryRegistry::new("HKEY_CLASSES_ROOT");
+    let class_key = r"CLSID\{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}";
+    let inproc_key = format!(r"{}\InprocServer32", class_key);
+    registry.create_key(&inproc_key).unwrap();
+    let name = RegValue::String("Other product".into());
+    registry.set_value(class_key, "", &name).unwrap();
+    let registration = AppRegistration {
+        custom_activator: Some("c3d7a5e8-0f3b-4d6c-9a8e-2b1f0e7d6c5a".into()),
+        launch_command: Some("app.exe".into()),
+        ..AppRegistration::new("App")
+    };
+    let plan = plan_register(&registry, &registration).unwrap();
+    assert!(!plan.changes.contains(&Change::CreateKey(class_key.into())));
+    plan.apply(&mut registry).unwrap();
+    let plan = plan_unregister(&registry, "App").unwrap();
+    assert_eq!(
+        plan.to_string(),
+        concat!(
+            "- [HKEY_CLASSES_ROOT\\AppUserModelId\\App]\n",
+            "- CreatedBy = \"win-toast-notifier\"\n",
+            "- CustomActivator = \"{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}\"\n",
+            "- DisplayName = \"App\"\n",
+            "- [HKEY_CLASSES_ROOT\\CLSID\\{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}\\LocalServer32]\n",
+            "- @ = \"app.exe\"\n",
+            "- CreatedBy = \"win-toast-notifier\"\n",
+        )
+    );
+    plan.apply(&mut registry).unwrap();
+    assert!(registry.key_exists(&inproc_key).unwrap());
+    assert_eq!(registry.get_value(class_key, "").unwrap(), Some(name));
+    let command_key = launch_command_key("{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}");
+    registry.create_key(&command_key).unwrap();
+    let command = RegValue::String("other.exe".into());
+    registry.set_value(&command_key, "", &command).unwrap();
+    plan_register(&registry, &AppRegistration::new("App"))
+        .unwrap()
+        .apply(&mut registry)
+        .unwrap();
+    let registration = AppRegistration {
+        custom_activator: registration.custom_activator,
+        ..AppRegistration::new("App")
+    };
+    plan_register(&registry, &registration)
+        .unwrap()
+        .apply(&mut registry)
+        .unwrap();
+    let plan = plan_unregister(&registry, "App").unwrap();
+    assert_eq!(plan.changes.len(), 1);
+    plan.apply(&mut registry).unwrap();
+    assert_eq!(registry.get_value(&command_key, "").unwrap(), Some(command));
+}
+
+#[test]
+fn register_foreign_launch_command_test() {
+    let mut registry = MemoryRegistry::new("HKEY_CLASSES_ROOT");
+    let command_key = launch_command_key("{C3D7A5E8-0F3B-4D6C-9A8E-2B1F0E7D6C5A}");
+    registry.create_key(&command_key).unwrap();
+    let command = RegValue::String("other.exe".into());
+    registry.set_value(&command_key, "", &command).unwrap();
+    let registration = AppRegistration {
+        custom_activator: Some("c3d7a5e8-0f3b-4d6c-9a8e-2b1f0e7d6c5a".into()),
+        launch_command: Some("app.exe".into()),
+        ..AppRegistration::new("App")
+    };
+    let error = plan_register(&registry, &registration).unwrap_err();
+    assert!(error.contains("registered by another product"), "{}", error);
+    let registration = AppRegistration {
+        launch_command: Some("other.exe".into()),
+        ..registration
+    };
+    let plan = plan_register(&registry, &registration).unwrap();
+    assert!(plan.changes.iter().all(|change| match change {
+        Change::CreateKey(key) | Change::SetValue { key, .. } => *key == app_id_key("App"),
+        _ => false,
+    }));
+    plan.apply(&mut registry).unwrap();
+    assert_eq!(registry.get_value(&command_key, "").unwrap(), Some(command));
+}
+
+#[test]
+fn scope_test() {
+    assert_eq!("user".parse::<Scope>(), Ok(Scope::User));
+    assert_eq!("machine".parse::<Scope>(), Ok(Scope::Machine));
+    assert!("system".parse::<Scope>().is_err());
+    assert_eq!(Scope::default(), Scope::User);
+    assert_eq!(
+        Scope::User.root_name(),
+        r"HKEY_CURRENT_USER\Software\Classes"
+    );
+    assert_eq!(Scope::Machine.to_string(), "machine");
+}
+
+#[test]
+fn sync_plan_test() {
+    let mut registry = MemoryRegistry::new("HKEY_CLASSES_ROOT");
+    let registrations = vec![
+        with_icon("First", r"C:\first.png"),
+        AppRegistration::new("Second"),
+    ];
+    plan_sync(&registry, &registrations, &[])
+        .unwrap()
+        .apply(&mut registry)
+        .unwrap();
+    registry.create_key(&app_id_key("Foreign")).unwrap();
+    assert!(plan_sync(&registry, &registrations, &[])
+        .unwrap()
+        .is_empty());
+    let registrations = vec![AppRegistration {
+        display_name: Some("First app".into()),
+        ..AppRegistration::new("first")
+    }];
+    let remove = unlisted_app_ids(&registry, &registrations).unwrap();
+    assert_eq!(remove, vec!["Second".to_string()]);
+    let plan = plan_sync(&registry, &registrations, &remove).unwrap();
+    assert_eq!(
+        plan.to_string(),
+        concat!(
+            "  [HKEY_CLASSES_ROOT\\AppUserModelId\\first]\n",
+            "- DisplayName = \"First\"\n",
+            "+ DisplayName = \"First app\"\n",
+            "- [HKEY_CLASSES_ROOT\\AppUserModelId\\Second]\n",
+            "- CreatedBy = \"win-toast-notifier\"\n",
+            "- DisplayName = \"Second\"\n",
+        )
+    );
+}
+
+#[test]
+fn icon_users_test() {
+    let mut registry = MemoryRegistry::new("HKEY_CLASSES_ROOT");
+    let registrations = vec![
+        with_icon("First", r"C:\Data\icon.png"),
+        with_icon("Second", r"C:\data\ICON.png"),
+        with_icon("Third", r"C:\other.png"),
+        AppRegistration::new("Fourth"),
+    ];
+    plan_sync(&registry, &registrations, &[])
+        .unwrap()
+        .apply(&mut registry)
+        .unwrap();
+    assert_eq!(
+        icon_users(&registry, r"C:\Data\icon.png").unwrap(),
+        vec!["First".to_string(), "Second".to_string()]
+    );
+    assert!(icon_users(&registry, r"C:\missing.png").unwrap().is_empty());
+}
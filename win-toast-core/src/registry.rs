@@ -0,0 +1,204 @@
+//! Access to registry keys and values below a root key.
+//!
+//! Windows implements [`Registry`] over the system registry, [`MemoryRegistry`] keeps keys in
+//! memory for tests and planning. Key paths are relative to the root and separated by `\`,
+//! key and value names are case insensitive.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::io;
+
+/// Registry value types used by application registrations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegValue {
+    String(String),
+    Dword(u32),
+}
+
+/// Formats value as in `.reg` files: `"text"` or `dword:0000000a`.
+impl Display for RegValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegValue::String(value) => write!(f, "\"{}\"", value),
+            RegValue::Dword(value) => write!(f, "dword:{:08x}", value),
+        }
+    }
+}
+
+pub trait Registry {
+    /// Name of the root key, such as `HKEY_CLASSES_ROOT`.
+    fn root_name(&self) -> String;
+    fn key_exists(&self, key: &str) -> io::Result<bool>;
+    /// Value of an existing key, None when the key or the value does not exist.
+    fn get_value(&self, key: &str, name: &str) -> io::Result<Option<RegValue>>;
+    /// Values of an existing key, empty when the key does not exist.
+    fn values(&self, key: &str) -> io::Result<Vec<(String, RegValue)>>;
+    /// Names of direct subkeys, empty when the key does not exist.
+    fn subkeys(&self, key: &str) -> io::Result<Vec<String>>;
+    /// Creates key and its missing parents.
+    fn create_key(&mut self, key: &str) -> io::Result<()>;
+    fn set_value(&mut self, key: &str, name: &str, value: &RegValue) -> io::Result<()>;
+    fn delete_value(&mut self, key: &str, name: &str) -> io::Result<()>;
+    /// Deletes key with its values and subkeys.
+    fn delete_key(&mut self, key: &str) -> io::Result<()>;
+}
+
+#[derive(Debug, Clone, Default)]
+struct MemoryKey {
+    /// Name of the key as it was created.
+    name: String,
+    /// Values by lower case name, with the name as it was set.
+    values: BTreeMap<String, (String, RegValue)>,
+}
+
+/// Registry kept in memory.
+#[derive(Debug, Clone)]
+pub struct MemoryRegistry {
+    root_name: String,
+    /// Keys by lower case path.
+    keys: BTreeMap<String, MemoryKey>,
+}
+
+impl MemoryRegistry {
+    pub fn new(root_name: &str) -> MemoryRegistry {
+        MemoryRegistry {
+            root_name: root_name.to_string(),
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+fn normalize(key: &str) -> String {
+    key.trim_matches('\\').to_lowercase()
+}
+
+fn not_found(key: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{} not found", key))
+}
+
+impl Registry for MemoryRegistry {
+    fn root_name(&self) -> String {
+        self.root_name.clone()
+    }
+
+    fn key_exists(&self, key: &str) -> io::Result<bool> {
+        Ok(self.keys.contains_key(&normalize(key)))
+    }
+
+    fn get_value(&self, key: &str, name: &str) -> io::Result<Option<RegValue>> {
+        Ok(self
+            .keys
+            .get(&normalize(key))
+            .and_then(|k| k.values.get(&name.to_lowercase()))
+            .map(|(_, value)| value.clone()))
+    }
+
+    fn values(&self, key: &str) -> io::Result<Vec<(String, RegValue)>> {
+        Ok(self
+            .keys
+            .get(&normalize(key))
+            .map(|k| k.values.values().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    fn subkeys(&self, key: &str) -> io::Result<Vec<String>> {
+        let prefix = format!("{}\\", normalize(key));
+        Ok(self
+            .keys
+            .iter()
+            .filter(|(path, _)| {
+                path.strip_prefix(&prefix)
+                    .is_some_and(|name| !name.contains('\\'))
+            })
+            .map(|(_, key)| key.name.clone())
+            .collect())
+    }
+
+    fn create_key(&mut self, key: &str) -> io::Result<()> {
+        let mut path = String::new();
+        for part in key.trim_matches('\\').split('\\') {
+            if !path.is_empty() {
+                path.push('\\');
+            }
+            path.push_str(&part.to_lowercase());
+            self.keys.entry(path.clone()).or_insert_with(|| MemoryKey {
+                name: part.to_string(),
+                values: BTreeMap::new(),
+            });
+        }
+        Ok(())
+    }
+
+    fn set_value(&mut self, key: &str, name: &str, value: &RegValue) -> io::Result<()> {
+        let entry = self.keys.get_mut(&normalize(key)).ok_or(not_found(key))?;
+        entry
+            .values
+            .insert(name.to_lowercase(), (name.to_string(), value.clone()));
+        Ok(())
+    }
+
+    fn delete_value(&mut self, key: &str, name: &str) -> io::Result<()> {
+        let entry = self.keys.get_mut(&normalize(key)).ok_or(not_found(key))?;
+        entry
+            .values
+            .remove(&name.to_lowercase())
+            .map(|_| ())
+            .ok_or(not_found(name))
+    }
+
+    fn delete_key(&mut self, key: &str) -> io::Result<()> {
+        let key = normalize(key);
+        if self.keys.remove(&key).is_none() {
+            return Err(not_found(&key));
+        }
+        let prefix = format!("{}\\", key);
+        self.keys.retain(|path, _| !path.starts_with(&prefix));
+        Ok(())
+    }
+}
+
+#[test]
+fn memory_registry_test() {
+    let mut registry = MemoryRegistry::new("HKEY_CLASSES_ROOT");
+    registry.create_key(r"AppUserModelId\App").unwrap();
+    assert!(registry.key_exists("appusermodelid").unwrap());
+    registry
+        .set_value(
+            r"AppUserModelId\App",
+            "DisplayName",
+            &RegValue::String("App".into()),
+        )
+        .unwrap();
+    assert_eq!(
+        registry
+            .get_value(r"appusermodelid\app", "displayname")
+            .unwrap(),
+        Some(RegValue::String("App".into()))
+    );
+    assert_eq!(
+        registry.values(r"AppUserModelId\App").unwrap(),
+        vec![("DisplayName".to_string(), RegValue::String("App".into()))]
+    );
+    assert!(registry
+        .set_value("Missing", "Name", &RegValue::Dword(1))
+        .is_err());
+    registry.create_key(r"AppUserModelId\Other\Nested").unwrap();
+    assert_eq!(
+        registry.subkeys("AppUserModelId").unwrap(),
+        vec!["App".to_string(), "Other".to_string()]
+    );
+    registry.delete_key("AppUserModelId").unwrap();
+    assert!(!registry.key_exists(r"AppUserModelId\App").unwrap());
+    assert_eq!(
+        registry
+            .get_value(r"AppUserModelId\App", "DisplayName")
+            .unwrap(),
+        None
+    );
+}
+
+#[test]
+fn reg_value_display_test() {
+    assert_eq!(RegValue::String("a".into()).to_string(), "\"a\"");
+    assert_eq!(RegValue::Dword(10).to_string(), "dword:0000000a");
+}
@@ -0,0 +1,277 @@
+//! Buffer of numbered items which keeps the latest ones within a [`Retention`].
+
+use std::collections::VecDeque;
+use std::mem::size_of;
+use std::time::{Duration, Instant};
+
+/// Limits of items kept by [`RingBuffer`], the oldest items are evicted first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Retention {
+    /// Maximum number of items.
+    pub max_count: usize,
+    /// Maximum approximate size of items in bytes. The newest item is kept even when it is larger.
+    pub max_bytes: Option<usize>,
+    /// Maximum time an item is kept after it was pushed.
+    pub max_age: Option<Duration>,
+}
+
+impl Retention {
+    /// Keeps the latest `max_count` items.
+    pub fn count(max_count: usize) -> Retention {
+        Retention {
+            max_count,
+            max_bytes: None,
+            max_age: None,
+        }
+    }
+}
+
+/// Approximate memory used by an item, used for [`Retention::max_bytes`].
+pub trait ApproximateSize {
+    fn approximate_size(&self) -> usize;
+}
+
+impl ApproximateSize for String {
+    fn approximate_size(&self) -> usize {
+        size_of::<String>() + self.len()
+    }
+}
+
+impl ApproximateSize for usize {
+    fn approximate_size(&self) -> usize {
+        size_of::<usize>()
+    }
+}
+
+struct Entry<T> {
+    item: T,
+    size: usize,
+    pushed_at: Instant,
+}
+
+/// Keeps the latest pushed items within its retention, each item gets the next number.
+pub struct RingBuffer<T> {
+    entries: VecDeque<Entry<T>>,
+    retention: Retention,
+    bytes: usize,
+    /// Number of the oldest retained item.
+    first_number: usize,
+}
+
+impl<T> RingBuffer<T> {
+    /// Creates buffer of `capacity` items which numbers its first item with 0.
+    pub fn new(capacity: usize) -> Self {
+        Self::starting_at(capacity, 0)
+    }
+
+    /// Creates buffer of `capacity` items which numbers its first item with `first_number`.
+    pub fn starting_at(capacity: usize, first_number: usize) -> Self {
+        Self::with_retention(Retention::count(capacity), first_number)
+    }
+
+    /// Creates buffer with the given retention which numbers its first item with `first_number`.
+    pub fn with_retention(retention: Retention, first_number: usize) -> Self {
+        RingBuffer {
+            entries: VecDeque::with_capacity(retention.max_count.min(1024)),
+            retention,
+            bytes: 0,
+            first_number,
+        }
+    }
+
+    /// Adds item, the oldest ones are evicted when the buffer is full.
+    pub fn push(&mut self, item: T) {
+        self.push_sized(item, size_of::<T>(), Instant::now())
+    }
+
+    /// Adds item of approximately `size` bytes pushed at `now` and evicts items out of retention.
+    pub fn push_sized(&mut self, item: T, size: usize, now: Instant) {
+        self.entries.push_back(Entry {
+            item,
+            size,
+            pushed_at: now,
+        });
+        self.bytes += size;
+        self.evict(now);
+    }
+
+    /// Evicts the oldest items until the buffer is within its retention at `now`.
+    pub fn evict(&mut self, now: Instant) {
+        while let Some(entry) = self.entries.front() {
+            let over_count = self.entries.len() > self.retention.max_count;
+            let over_bytes = self.entries.len() > 1
+                && self.retention.max_bytes.is_some_and(|max| self.bytes > max);
+            if !(over_count || over_bytes || self.is_expired(entry, now)) {
+                break;
+            }
+            self.bytes -= entry.size;
+            self.entries.pop_front();
+            self.first_number += 1;
+        }
+    }
+
+    /// Whether the oldest item is older than the maximum age at `now`.
+    pub fn has_expired(&self, now: Instant) -> bool {
+        self.entries
+            .front()
+            .is_some_and(|entry| self.is_expired(entry, now))
+    }
+
+    fn is_expired(&self, entry: &Entry<T>, now: Instant) -> bool {
+        self.retention
+            .max_age
+            .is_some_and(|max_age| now.saturating_duration_since(entry.pushed_at) >= max_age)
+    }
+
+    /// Retained items, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter().map(|entry| &entry.item)
+    }
+    /// Number of the next pushed item.
+    pub fn get_next_number(&self) -> usize {
+        self.first_number + self.entries.len()
+    }
+    /// Retained item with the given number.
+    pub fn get(&self, number: usize) -> Option<&T> {
+        let index = number.checked_sub(self.first_number)?;
+        self.entries.get(index).map(|entry| &entry.item)
+    }
+    /// Number of the oldest retained item, the next number when nothing is retained.
+    pub fn get_base_index(&self) -> usize {
+        self.first_number
+    }
+    /// Approximate size of retained items in bytes.
+    pub fn approximate_bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+#[test]
+fn buffer_test() {
+    let mut ring_buffer = RingBuffer::new(3);
+    ring_buffer.push(0);
+    assert_eq!(ring_buffer.iter().copied().collect::<Vec<i32>>(), vec![0]);
+    ring_buffer.push(1);
+    assert_eq!(
+        ring_buffer.iter().copied().collect::<Vec<i32>>(),
+        vec![0, 1]
+    );
+    ring_buffer.push(2);
+    assert_eq!(
+        ring_buffer.iter().copied().collect::<Vec<i32>>(),
+        vec![0, 1, 2]
+    );
+    ring_buffer.push(3);
+    assert_eq!(
+        ring_buffer.iter().copied().collect::<Vec<i32>>(),
+        vec![1, 2, 3]
+    );
+    ring_buffer.push(4);
+    assert_eq!(
+        ring_buffer.iter().copied().collect::<Vec<i32>>(),
+        vec![2, 3, 4]
+    );
+}
+
+#[test]
+fn get_base_index_test() {
+    let mut ring_buffer = RingBuffer::new(3);
+    for i in 0..10 {
+        ring_buffer.push(i);
+        let base_index = ring_buffer.get_base_index();
+        let last_number = ring_buffer
+            .iter()
+            .enumerate()
+            .map(|(id, _)| id + base_index)
+            .last()
+            .unwrap();
+        assert_eq!(i, last_number);
+    }
+}
+
+#[test]
+fn starting_at_test() {
+    let mut ring_buffer = RingBuffer::starting_at(3, 10);
+    assert_eq!(ring_buffer.get_base_index(), 10);
+    assert_eq!(ring_buffer.get_next_number(), 10);
+    for i in 10..15 {
+        ring_buffer.push(i);
+    }
+    assert_eq!(ring_buffer.get_base_index(), 12);
+    assert_eq!(ring_buffer.get_next_number(), 15);
+    assert_eq!(
+        ring_buffer.iter().copied().collect::<Vec<usize>>(),
+        vec![12, 13, 14]
+    );
+}
+
+#[test]
+fn get_by_number_test() {
+    let mut ring_buffer = RingBuffer::starting_at(3, 10);
+    assert_eq!(ring_buffer.get(10), None);
+    for i in 10..15 {
+        ring_buffer.push(i);
+    }
+    assert_eq!(ring_buffer.get(11), None);
+    assert_eq!(ring_buffer.get(12), Some(&12));
+    assert_eq!(ring_buffer.get(14), Some(&14));
+    assert_eq!(ring_buffer.get(15), None);
+}
+
+#[test]
+fn zero_capacity_test() {
+    let mut ring_buffer = RingBuffer::new(0);
+    ring_buffer.push(1);
+    assert_eq!(ring_buffer.get_base_index(), 1);
+    assert_eq!(ring_buffer.get_next_number(), 1);
+    assert_eq!(ring_buffer.get(0), None);
+}
+
+#[test]
+fn retention_by_bytes_test() {
+    let retention = Retention {
+        max_bytes: Some(10),
+        ..Retention::count(100)
+    };
+    let mut ring_buffer = RingBuffer::with_retention(retention, 0);
+    let now = Instant::now();
+    for i in 0..5 {
+        ring_buffer.push_sized(i, 4, now);
+    }
+    assert_eq!(
+        ring_buffer.iter().copied().collect::<Vec<i32>>(),
+        vec![3, 4]
+    );
+    assert_eq!(ring_buffer.get_base_index(), 3);
+    assert_eq!(ring_buffer.approximate_bytes(), 8);
+    ring_buffer.push_sized(5, 50, now);
+    assert_eq!(ring_buffer.iter().copied().collect::<Vec<i32>>(), vec![5]);
+    assert_eq!(ring_buffer.get(5), Some(&5));
+    assert_eq!(ring_buffer.get_base_index(), 5);
+}
+
+#[test]
+fn retention_by_age_test() {
+    let retention = Retention {
+        max_age: Some(Duration::from_secs(10)),
+        ..Retention::count(100)
+    };
+    let mut ring_buffer = RingBuffer::with_retention(retention, 7);
+    let start = Instant::now();
+    for i in 0..4 {
+        ring_buffer.push_sized(i, 1, start + Duration::from_secs(i * 5));
+    }
+    // Items pushed at 0s and 5s expired when the item pushed at 15s arrived.
+    assert_eq!(ring_buffer.get_base_index(), 9);
+    assert!(!ring_buffer.has_expired(start + Duration::from_secs(19)));
+    assert!(ring_buffer.has_expired(start + Duration::from_secs(20)));
+    ring_buffer.evict(start + Duration::from_secs(22));
+    assert_eq!(ring_buffer.iter().copied().collect::<Vec<u64>>(), vec![3]);
+    assert_eq!(ring_buffer.get_base_index(), 10);
+    assert_eq!(ring_buffer.get(9), None);
+    assert_eq!(ring_buffer.get(10), Some(&3));
+    ring_buffer.evict(start + Duration::from_secs(60));
+    assert_eq!(ring_buffer.get_base_index(), 11);
+    assert_eq!(ring_buffer.get_next_number(), 11);
+    assert_eq!(ring_buffer.approximate_bytes(), 0);
+}
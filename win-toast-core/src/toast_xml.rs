@@ -0,0 +1,41 @@
+//! Toast notification XML building.
+
+/// Creates generic toast with title, message and buttons from comma separated `buttons`,
+/// each button is activated with its caption as arguments.
+pub fn create_sample_notification(title: &str, message: &str, buttons: Option<String>) -> String {
+    let mut actions = String::new();
+    if let Some(btn) = buttons {
+        let actions_list: Vec<String> = btn
+            .split(',')
+            .map(|b| format!("<action arguments=\"{}\" content=\"{}\"/>", b, b).to_string())
+            .collect();
+        if !actions_list.is_empty() {
+            actions.push_str("<actions>");
+            actions.push_str(actions_list.join("\n").as_str());
+            actions.push_str("</actions>");
+        }
+    }
+    let string = format!(
+        "
+<toast>
+  <visual>
+    <binding template=\"ToastGeneric\">
+      <text>{}</text>
+      <text>{}</text>
+    </binding>
+  </visual>
+    {}
+</toast>",
+        title, message, actions
+    )
+    .to_string();
+    string
+}
+
+#[test]
+fn create_sample_notification_test() {
+    let res = create_sample_notification("hello", "world", Some("yes,no".to_string()));
+    assert!(res.contains("<text>hello</text>"));
+    assert!(res.contains("<action arguments=\"yes\" content=\"yes\"/>"));
+    assert!(res.contains("<action arguments=\"no\" content=\"no\"/>"));
+}